@@ -0,0 +1,131 @@
+use std::{sync::Arc, time::Duration};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use my_http_server::HttpFailResult;
+use my_socket_io_middleware::{
+    MySocketIoConnection, MySocketIoConnectionsCallbacks, MySocketIoEngineMiddleware,
+    MySocketIoJsonParser, MySocketIoMetrics, MySocketIoSendQueueOptions, NamespaceRouter,
+};
+
+// Incoming events go through the same dispatch for WebSocket frames and polling POSTs:
+// decode, rate limits, middlewares and the namespace router handler
+const CONNECTIONS: [usize; 2] = [10_000, 100_000];
+const EVENTS_PER_ITERATION: usize = 1_000;
+const WORKERS: usize = 8;
+const EVENT_PAYLOAD: &str = "42[\"ping\",{\"value\":1}]";
+
+struct BenchConnectionsCallbacks;
+
+#[async_trait::async_trait]
+impl MySocketIoConnectionsCallbacks for BenchConnectionsCallbacks {
+    async fn connected(&self, _socket_io: Arc<MySocketIoConnection>) -> Result<(), HttpFailResult> {
+        Ok(())
+    }
+
+    async fn disconnected(&self, _socket_io: Arc<MySocketIoConnection>) {}
+}
+
+async fn create_engine(connections: usize) -> Arc<MySocketIoEngineMiddleware> {
+    let engine = MySocketIoEngineMiddleware::new(Arc::new(BenchConnectionsCallbacks));
+
+    let mut router = NamespaceRouter::new("/");
+    router.on_raw("ping", |event, _ack| async move {
+        std::hint::black_box(event);
+        Ok(())
+    });
+    engine.register_socket_io(Arc::new(router)).await;
+
+    let list = engine.get_socket_io_list();
+    let metrics = Arc::new(MySocketIoMetrics::new());
+
+    for i in 0..connections {
+        let socket_io = Arc::new(MySocketIoConnection::new(
+            format!("sid{}", i),
+            None,
+            None,
+            MySocketIoSendQueueOptions::default(),
+            Arc::new(MySocketIoJsonParser),
+            metrics.clone(),
+        ));
+
+        // Events are dispatched only to joined namespaces
+        socket_io.add_namespace("/").await;
+        list.add_socket_io(socket_io);
+    }
+
+    Arc::new(engine)
+}
+
+async fn dispatch(engine: &MySocketIoEngineMiddleware, connections: usize, seed: usize) {
+    for i in 0..EVENTS_PER_ITERATION {
+        let sid = format!("sid{}", (seed + i * 7919) % connections);
+        engine.receive_packets(sid.as_str(), EVENT_PAYLOAD).await;
+    }
+}
+
+fn bench_dispatch(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(WORKERS)
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let mut group = c.benchmark_group("event_dispatch");
+
+    for connections in CONNECTIONS {
+        let engine = runtime.block_on(create_engine(connections));
+
+        group.throughput(Throughput::Elements(EVENTS_PER_ITERATION as u64));
+        group.bench_with_input(
+            BenchmarkId::new("single_task", connections),
+            &connections,
+            |b, connections| {
+                let mut seed = 0;
+                b.iter(|| {
+                    seed += 1;
+                    runtime.block_on(dispatch(&engine, *connections, seed));
+                })
+            },
+        );
+
+        // Every worker dispatches to its own set of connections at the same time
+        group.throughput(Throughput::Elements(
+            (EVENTS_PER_ITERATION * WORKERS) as u64,
+        ));
+        group.bench_with_input(
+            BenchmarkId::new("parallel_tasks", connections),
+            &connections,
+            |b, connections| {
+                let mut seed = 0;
+                b.iter(|| {
+                    seed += 1;
+                    runtime.block_on(async {
+                        let tasks: Vec<_> = (0..WORKERS)
+                            .map(|worker_no| {
+                                let engine = engine.clone();
+                                let connections = *connections;
+                                let seed = seed + worker_no * 31;
+                                tokio::spawn(async move {
+                                    dispatch(&engine, connections, seed).await;
+                                })
+                            })
+                            .collect();
+
+                        for task in tasks {
+                            task.await.unwrap();
+                        }
+                    });
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().measurement_time(Duration::from_secs(5));
+    targets = bench_dispatch
+}
+criterion_main!(benches);
@@ -0,0 +1,88 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use socket_io_utils::my_socket_io_messages::{MySocketIoMessage, MySocketIoTextPayload};
+
+use crate::{
+    pending_dispatch_guard::PendingDispatchGuard, MySocketIoConnection, MySocketIoError,
+    DEFAULT_NAMESPACE,
+};
+
+// Exists only if the client has requested an ack. Can be moved to another task and answered later.
+// Clones share the state, so only the first answer is sent.
+// Dispatch stays pending for the graceful shutdown until the answer is sent or the last clone is dropped
+#[derive(Clone)]
+pub struct MySocketIoAckResponder {
+    socket_io: Arc<MySocketIoConnection>,
+    nsp: Option<String>,
+    id: i64,
+    started: Instant,
+    responded: Arc<AtomicBool>,
+    pending_dispatch: Arc<PendingDispatchGuard>,
+}
+
+impl MySocketIoAckResponder {
+    pub(crate) fn new(
+        socket_io: Arc<MySocketIoConnection>,
+        nsp: Option<String>,
+        id: i64,
+        started: Instant,
+        pending_dispatch: Arc<PendingDispatchGuard>,
+    ) -> Self {
+        Self {
+            socket_io,
+            nsp,
+            id,
+            started,
+            responded: Arc::new(AtomicBool::new(false)),
+            pending_dispatch,
+        }
+    }
+
+    pub fn get_id(&self) -> i64 {
+        self.id
+    }
+
+    pub fn is_responded(&self) -> bool {
+        self.responded.load(Ordering::Relaxed)
+    }
+
+    // data is the json array of ack arguments. Returns false if the ack has already been sent
+    pub async fn send(&self, data: String) -> bool {
+        if self.responded.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+
+        self.socket_io.get_metrics().handler_duration(
+            self.nsp.as_deref().unwrap_or(DEFAULT_NAMESPACE),
+            self.started.elapsed(),
+        );
+
+        let ack_contract = MySocketIoMessage::Ack(MySocketIoTextPayload {
+            nsp: self.nsp.clone(),
+            data,
+            id: Some(self.id),
+        });
+
+        self.socket_io.send_message(&ack_contract).await;
+        self.pending_dispatch.release();
+        true
+    }
+
+    pub async fn send_error(&self, err: &MySocketIoError) -> bool {
+        if self.responded.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+
+        self.socket_io
+            .send_error(self.nsp.clone(), Some(self.id), err)
+            .await;
+        self.pending_dispatch.release();
+        true
+    }
+}
@@ -0,0 +1,143 @@
+use std::{collections::HashMap, sync::Arc};
+
+use hyper::Method;
+use my_http_server::{HttpContext, HttpFailResult, HttpOkResult, HttpOutput, WebContentType};
+
+use crate::{MySocketIoConnection, MySocketIoEngineMiddleware};
+
+#[async_trait::async_trait]
+pub trait MySocketIoAdminAuth {
+    async fn authorize(&self, ctx: &mut HttpContext) -> bool;
+}
+
+#[derive(Clone)]
+pub struct MySocketIoAdminOptions {
+    pub path: String,
+    pub auth: Arc<dyn MySocketIoAdminAuth + Send + Sync + 'static>,
+}
+
+// Plain HTTP JSON endpoint. It is not the Socket.IO admin UI protocol which works over the /admin namespace.
+// Routes relative to the admin path:
+// GET  /sockets               - every connection with joined namespaces and their rooms
+// GET  /namespaces            - namespaces with sockets count
+// POST /sockets/disconnect?id - disconnects session
+pub async fn handle_admin_request(
+    engine: &MySocketIoEngineMiddleware,
+    admin: &MySocketIoAdminOptions,
+    ctx: &mut HttpContext,
+) -> Result<HttpOkResult, HttpFailResult> {
+    if !admin.auth.authorize(ctx).await {
+        return Err(fail_result(401, "Unauthorized"));
+    }
+
+    let path = ctx.request.get_path().to_string();
+    let route = &path[admin.path.trim_end_matches('/').len()..];
+
+    let method = ctx.request.method.clone();
+
+    match (&method, route.trim_end_matches('/')) {
+        (&Method::GET, "/sockets") => {
+            let mut result = Vec::new();
+
+            // Connection which has not joined any namespace yet is listed too with namespaces: []
+            for socket_io in engine.get_connections().await {
+                result.push(serialize_socket(&socket_io).await);
+            }
+
+            json_result(serde_json::Value::Array(result))
+        }
+        (&Method::GET, "/namespaces") => {
+            let mut namespaces: HashMap<String, usize> = HashMap::new();
+
+            for socket_io in engine.get_connections().await {
+                for nsp in socket_io.get_namespaces().await {
+                    *namespaces.entry(nsp).or_insert(0) += 1;
+                }
+            }
+
+            let result = namespaces
+                .into_iter()
+                .map(|(name, sockets_count)| {
+                    serde_json::json!({
+                        "name": name,
+                        "socketsCount": sockets_count,
+                    })
+                })
+                .collect();
+
+            json_result(serde_json::Value::Array(result))
+        }
+        (&Method::POST, "/sockets/disconnect") => {
+            let query = ctx.request.get_query_string()?;
+
+            let sid = match query.get_optional("id") {
+                Some(sid) => sid.value.to_string(),
+                None => return Err(fail_result(400, "Query parameter id is required")),
+            };
+
+            if engine.disconnect_socket_io(sid.as_str()).await {
+                json_result(serde_json::json!({ "id": sid, "disconnected": true }))
+            } else {
+                Err(fail_result(404, "Socket.IO connection is not found"))
+            }
+        }
+        _ => Err(fail_result(404, "Not found")),
+    }
+}
+
+// "/admin" matches "/admin" and "/admin/sockets" but not "/administrator" or "/admin-foo"
+pub fn is_admin_path(path: &str, admin_path: &str) -> bool {
+    match path.strip_prefix(admin_path.trim_end_matches('/')) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+async fn serialize_socket(socket_io: &Arc<MySocketIoConnection>) -> serde_json::Value {
+    let remote_addr = socket_io.remote_addr.map(|addr| addr.ip().to_string());
+
+    let mut namespaces = Vec::new();
+    for nsp in socket_io.get_namespaces().await {
+        let rooms = socket_io.get_rooms(nsp.as_str()).await;
+        namespaces.push(serde_json::json!({
+            "name": nsp,
+            "rooms": rooms,
+        }));
+    }
+
+    let transport = socket_io.get_transport().as_str();
+    let data = socket_io.get_all_data();
+
+    serde_json::json!({
+        "id": socket_io.id,
+        "clientId": socket_io.id,
+        "transport": transport,
+        "namespaces": namespaces,
+        "data": data,
+        "handshake": {
+            "address": remote_addr,
+            "time": socket_io.created.to_rfc3339(),
+            "issued": socket_io.created.unix_microseconds / 1000,
+        },
+        "lastIncomingMoment": socket_io.last_incoming_moment.as_date_time().to_rfc3339(),
+    })
+}
+
+fn json_result(value: serde_json::Value) -> Result<HttpOkResult, HttpFailResult> {
+    HttpOutput::Content {
+        headers: None,
+        content_type: Some(WebContentType::Json),
+        content: value.to_string().into_bytes(),
+    }
+    .into_ok_result(false)
+    .into()
+}
+
+fn fail_result(status_code: u16, message: &str) -> HttpFailResult {
+    HttpFailResult {
+        write_telemetry: false,
+        content_type: WebContentType::Text,
+        status_code,
+        content: message.to_string().into_bytes(),
+    }
+}
@@ -0,0 +1,15 @@
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default)]
+pub struct MySocketIoBroadcastAckResult {
+    // sid -> json array of ack arguments
+    pub responses: HashMap<String, String>,
+    pub timed_out: Vec<String>,
+    pub disconnected: Vec<String>,
+}
+
+impl MySocketIoBroadcastAckResult {
+    pub fn is_all_acknowledged(&self) -> bool {
+        self.timed_out.is_empty() && self.disconnected.is_empty()
+    }
+}
@@ -0,0 +1,55 @@
+use std::io::Write;
+
+use flate2::{
+    write::{GzEncoder, ZlibEncoder},
+    Compression,
+};
+
+// Long-polling responses only. WebSocket frames are not compressed: permessage-deflate is not
+// negotiated since the upgrade is done by my-http-server-web-sockets. It is a separate follow-up
+#[derive(Debug, Clone, Copy)]
+pub struct MySocketIoCompressionOptions {
+    // Compress long-polling responses if the client sends Accept-Encoding with gzip or deflate
+    pub http_compression: bool,
+    // Long-polling responses smaller than threshold are sent as is
+    pub threshold: usize,
+}
+
+impl Default for MySocketIoCompressionOptions {
+    fn default() -> Self {
+        Self {
+            http_compression: true,
+            threshold: 1024,
+        }
+    }
+}
+
+// Returns Content-Encoding and compressed content
+pub fn compress_http_payload(
+    options: &MySocketIoCompressionOptions,
+    accept_encoding: &str,
+    payload: &[u8],
+) -> Option<(&'static str, Vec<u8>)> {
+    if !options.http_compression || payload.len() < options.threshold {
+        return None;
+    }
+
+    let accepted: Vec<&str> = accept_encoding
+        .split(',')
+        .map(|itm| itm.split(';').next().unwrap_or("").trim())
+        .collect();
+
+    if accepted.contains(&"gzip") {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(payload).ok()?;
+        return Some(("gzip", encoder.finish().ok()?));
+    }
+
+    if accepted.contains(&"deflate") {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(payload).ok()?;
+        return Some(("deflate", encoder.finish().ok()?));
+    }
+
+    None
+}
@@ -0,0 +1,26 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MySocketIoDisconnectReason {
+    TransportClose,
+    PingTimeout,
+    ProtocolError,
+    ServerShutdown,
+    ServerDisconnect,
+    RateLimited,
+    PayloadTooLarge,
+    SlowConsumer,
+}
+
+impl MySocketIoDisconnectReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::TransportClose => "transport_close",
+            Self::PingTimeout => "ping_timeout",
+            Self::ProtocolError => "protocol_error",
+            Self::ServerShutdown => "server_shutdown",
+            Self::ServerDisconnect => "server_disconnect",
+            Self::RateLimited => "rate_limited",
+            Self::PayloadTooLarge => "payload_too_large",
+            Self::SlowConsumer => "slow_consumer",
+        }
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use regex::Regex;
+
+use crate::{MySocketIo, MySocketIoMiddleware};
+
+pub type MySocketIoNamespaceFactory =
+    dyn Fn(&str) -> Option<Arc<dyn MySocketIo + Send + Sync + 'static>> + Send + Sync + 'static;
+
+enum NamespaceMatcher {
+    Regex(Regex),
+    Predicate(Box<dyn Fn(&str) -> bool + Send + Sync + 'static>),
+}
+
+// Parent of namespaces which are created on the first RequestAccess with a matching name ("/tenant-42").
+// Factory gets the requested name and can still reject it by returning None
+pub struct MySocketIoDynamicNamespace {
+    matcher: NamespaceMatcher,
+    factory: Box<MySocketIoNamespaceFactory>,
+    middlewares: Vec<Arc<dyn MySocketIoMiddleware + Send + Sync + 'static>>,
+    cleanup_when_empty: bool,
+}
+
+impl MySocketIoDynamicNamespace {
+    // Pattern is matched against the whole namespace name including leading '/': "^/tenant-\d+$"
+    pub fn with_regex(
+        pattern: &str,
+        factory: impl Fn(&str) -> Option<Arc<dyn MySocketIo + Send + Sync + 'static>>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Result<Self, regex::Error> {
+        let regex = Regex::new(pattern)?;
+        Ok(Self::new(NamespaceMatcher::Regex(regex), Box::new(factory)))
+    }
+
+    pub fn with_predicate(
+        predicate: impl Fn(&str) -> bool + Send + Sync + 'static,
+        factory: impl Fn(&str) -> Option<Arc<dyn MySocketIo + Send + Sync + 'static>>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        Self::new(
+            NamespaceMatcher::Predicate(Box::new(predicate)),
+            Box::new(factory),
+        )
+    }
+
+    fn new(matcher: NamespaceMatcher, factory: Box<MySocketIoNamespaceFactory>) -> Self {
+        Self {
+            matcher,
+            factory,
+            middlewares: Vec::new(),
+            cleanup_when_empty: false,
+        }
+    }
+
+    // Middleware is executed on connect to every child namespace before middlewares registered by exact name
+    pub fn with_middleware(
+        mut self,
+        middleware: Arc<dyn MySocketIoMiddleware + Send + Sync + 'static>,
+    ) -> Self {
+        self.middlewares.push(middleware);
+        self
+    }
+
+    // Child namespace is removed when its last connection is gone and is created again on the next request
+    pub fn cleanup_when_empty(mut self, value: bool) -> Self {
+        self.cleanup_when_empty = value;
+        self
+    }
+
+    pub fn is_match(&self, nsp: &str) -> bool {
+        match &self.matcher {
+            NamespaceMatcher::Regex(regex) => regex.is_match(nsp),
+            NamespaceMatcher::Predicate(predicate) => predicate(nsp),
+        }
+    }
+
+    pub(crate) fn create(&self, nsp: &str) -> Option<Arc<dyn MySocketIo + Send + Sync + 'static>> {
+        (self.factory)(nsp)
+    }
+
+    pub(crate) fn get_middlewares(
+        &self,
+    ) -> &[Arc<dyn MySocketIoMiddleware + Send + Sync + 'static>] {
+        &self.middlewares
+    }
+
+    pub(crate) fn is_cleanup_when_empty(&self) -> bool {
+        self.cleanup_when_empty
+    }
+}
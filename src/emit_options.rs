@@ -0,0 +1,31 @@
+#[derive(Debug, Clone, Copy)]
+pub struct MySocketIoEmitOptions {
+    // Packet is discarded instead of queued if the connection is not writable right now
+    pub volatile: bool,
+    // false - long-polling response with this packet is not compressed. No effect on WebSocket,
+    // see MySocketIoCompressionOptions
+    pub compress: bool,
+}
+
+impl MySocketIoEmitOptions {
+    pub fn volatile() -> Self {
+        Self {
+            volatile: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn compress(mut self, compress: bool) -> Self {
+        self.compress = compress;
+        self
+    }
+}
+
+impl Default for MySocketIoEmitOptions {
+    fn default() -> Self {
+        Self {
+            volatile: false,
+            compress: true,
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::{
+    MySocketIoAdminOptions, MySocketIoCompressionOptions, MySocketIoJsonParser, MySocketIoParser,
+    MySocketIoProtocolErrorPolicy, MySocketIoRateLimitOptions, MySocketIoSendQueueOptions,
+};
+
+#[derive(Clone)]
+pub struct MySocketIoEngineOptions {
+    // Max size of a single WebSocket frame or long-polling POST body.
+    // POST body is read chunk by chunk and rejected with 413 as soon as it is over the limit.
+    // WebSocket frame is checked after my-http-server-web-sockets has received it: its handle_web_socket_upgrade
+    // does not take a tungstenite WebSocketConfig, so max_message_size can not be set at upgrade yet
+    pub max_http_buffer_size: usize,
+    // Max size of the json of one EVENT packet. It is checked after max_http_buffer_size,
+    // so the smaller of the two is the effective limit for an event
+    pub max_event_packet_size: usize,
+    pub max_event_args: usize,
+    pub protocol_error_policy: MySocketIoProtocolErrorPolicy,
+    pub admin: Option<MySocketIoAdminOptions>,
+    pub rate_limit: MySocketIoRateLimitOptions,
+    pub send_queue: MySocketIoSendQueueOptions,
+    pub parser: Arc<dyn MySocketIoParser + Send + Sync + 'static>,
+    pub compression: MySocketIoCompressionOptions,
+    // Polling connection falls back to polling if upgrade packet does not come within this time
+    pub upgrade_timeout: Duration,
+    // Connection data key which holds the user id. Used to find connections of a user
+    pub user_id_data_key: String,
+}
+
+impl Default for MySocketIoEngineOptions {
+    fn default() -> Self {
+        Self {
+            max_http_buffer_size: 1_000_000,
+            max_event_packet_size: 1024 * 1024,
+            max_event_args: 16,
+            protocol_error_policy: MySocketIoProtocolErrorPolicy::ReplyError,
+            admin: None,
+            rate_limit: MySocketIoRateLimitOptions::default(),
+            send_queue: MySocketIoSendQueueOptions::default(),
+            parser: Arc::new(MySocketIoJsonParser),
+            compression: MySocketIoCompressionOptions::default(),
+            upgrade_timeout: Duration::from_secs(10),
+            user_id_data_key: "userId".to_string(),
+        }
+    }
+}
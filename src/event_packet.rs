@@ -0,0 +1,200 @@
+use my_json::json_reader::array_parser::ArrayToJsonObjectsSplitter;
+
+use crate::MySocketIoError;
+
+pub const PROTOCOL_ERROR: &str = "Protocol error";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MySocketIoProtocolErrorPolicy {
+    Ignore,
+    ReplyError,
+    Disconnect,
+}
+
+#[derive(Debug)]
+pub enum MySocketIoProtocolError {
+    PacketTooLarge { size: usize, max_size: usize },
+    InvalidJson(String),
+    InvalidUtf8,
+    InvalidMsgPack(String),
+    MissingEventName,
+    EventNameIsNotString,
+    TooManyArguments { count: usize, max_count: usize },
+}
+
+impl MySocketIoProtocolError {
+    pub fn to_socket_io_error(&self) -> MySocketIoError {
+        let reason = match self {
+            Self::PacketTooLarge { size, max_size } => {
+                format!("Packet size {} exceeds max size {}", size, max_size)
+            }
+            Self::InvalidJson(err) => format!("Invalid json: {}", err),
+            Self::InvalidUtf8 => "Invalid utf8".to_string(),
+            Self::InvalidMsgPack(err) => format!("Invalid MessagePack: {}", err),
+            Self::MissingEventName => "Event name is missing".to_string(),
+            Self::EventNameIsNotString => "Event name must be a string".to_string(),
+            Self::TooManyArguments { count, max_count } => {
+                format!("Arguments count {} exceeds max count {}", count, max_count)
+            }
+        };
+
+        MySocketIoError::with_data(
+            PROTOCOL_ERROR,
+            serde_json::json!({ "reason": reason }).to_string(),
+        )
+    }
+}
+
+pub struct EventPacket {
+    pub event_name: String,
+    pub event_data: String,
+    pub args: Vec<String>,
+}
+
+// data is the json array of Socket.IO EVENT packet: ["event_name", arg1, arg2, ...]
+pub fn parse_event_packet(
+    data: &str,
+    max_packet_size: usize,
+    max_args: usize,
+) -> Result<EventPacket, MySocketIoProtocolError> {
+    if data.len() > max_packet_size {
+        return Err(MySocketIoProtocolError::PacketTooLarge {
+            size: data.len(),
+            max_size: max_packet_size,
+        });
+    }
+
+    let mut event_name = None;
+    let mut args = Vec::new();
+
+    for (i, item) in data.as_bytes().split_array_json_to_objects().enumerate() {
+        let item = match item {
+            Ok(item) => item,
+            Err(err) => return Err(MySocketIoProtocolError::InvalidJson(format!("{:?}", err))),
+        };
+
+        let item = match std::str::from_utf8(item) {
+            Ok(item) => item,
+            Err(_) => return Err(MySocketIoProtocolError::InvalidUtf8),
+        };
+
+        if i == 0 {
+            event_name = Some(parse_event_name(item)?);
+            continue;
+        }
+
+        if args.len() == max_args {
+            return Err(MySocketIoProtocolError::TooManyArguments {
+                count: args.len() + 1,
+                max_count: max_args,
+            });
+        }
+
+        args.push(item.to_string());
+    }
+
+    let event_name = match event_name {
+        Some(event_name) => event_name,
+        None => return Err(MySocketIoProtocolError::MissingEventName),
+    };
+
+    let event_data = match args.first() {
+        Some(first) => first.clone(),
+        None => "null".to_string(),
+    };
+
+    Ok(EventPacket {
+        event_name,
+        event_data,
+        args,
+    })
+}
+
+fn parse_event_name(item: &str) -> Result<String, MySocketIoProtocolError> {
+    if !item.trim_start().starts_with('"') {
+        return Err(MySocketIoProtocolError::EventNameIsNotString);
+    }
+
+    match serde_json::from_str::<String>(item) {
+        Ok(event_name) => Ok(event_name),
+        Err(err) => Err(MySocketIoProtocolError::InvalidJson(err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_with_several_arguments() {
+        let packet = parse_event_packet(r#"["order",{"id":1},[1,2],"text"]"#, 1024, 16).unwrap();
+
+        assert_eq!("order", packet.event_name);
+        assert_eq!(r#"{"id":1}"#, packet.event_data);
+        assert_eq!(vec![r#"{"id":1}"#, "[1,2]", r#""text""#], packet.args);
+    }
+
+    #[test]
+    fn test_event_without_arguments() {
+        let packet = parse_event_packet(r#"["ping"]"#, 1024, 16).unwrap();
+
+        assert_eq!("ping", packet.event_name);
+        assert_eq!("null", packet.event_data);
+        assert!(packet.args.is_empty());
+    }
+
+    #[test]
+    fn test_packet_too_large() {
+        let data = r#"["order",{"id":1}]"#;
+        let result = parse_event_packet(data, data.len() - 1, 16);
+
+        assert!(matches!(
+            result,
+            Err(MySocketIoProtocolError::PacketTooLarge { size, max_size })
+                if size == data.len() && max_size == data.len() - 1
+        ));
+    }
+
+    #[test]
+    fn test_packet_of_max_size_is_accepted() {
+        let data = r#"["order",{"id":1}]"#;
+        assert!(parse_event_packet(data, data.len(), 16).is_ok());
+    }
+
+    #[test]
+    fn test_too_many_arguments() {
+        let result = parse_event_packet(r#"["order",1,2,3]"#, 1024, 2);
+
+        assert!(matches!(
+            result,
+            Err(MySocketIoProtocolError::TooManyArguments {
+                count: 3,
+                max_count: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_max_arguments_are_accepted() {
+        let packet = parse_event_packet(r#"["order",1,2]"#, 1024, 2).unwrap();
+        assert_eq!(vec!["1", "2"], packet.args);
+    }
+
+    #[test]
+    fn test_missing_event_name() {
+        let result = parse_event_packet("[]", 1024, 16);
+        assert!(matches!(
+            result,
+            Err(MySocketIoProtocolError::MissingEventName)
+        ));
+    }
+
+    #[test]
+    fn test_event_name_is_not_string() {
+        let result = parse_event_packet("[1,2]", 1024, 16);
+        assert!(matches!(
+            result,
+            Err(MySocketIoProtocolError::EventNameIsNotString)
+        ));
+    }
+}
@@ -1,28 +1,111 @@
+use std::{collections::HashMap, time::Duration};
+
 use my_http_server::{HttpFailResult, HttpOkResult, HttpOutput, WebContentType};
 
-use crate::app::AppContext;
+use crate::{MySocketIoCompressionOptions, SocketIoList};
+
+pub const ENGINE_IO_NOOP_PAYLOAD: &str = "6";
 
-pub async fn handle_long_pool(app: &AppContext, sid: &str) -> Result<HttpOkResult, HttpFailResult> {
-    let web_socket = app.socket_io_list.get(sid).await;
+pub struct LongPoolPayload {
+    pub payload: String,
+    // false if every packet of the payload was emitted with compress(false)
+    pub compress: bool,
+}
 
-    if web_socket.is_none() {
+impl LongPoolPayload {
+    pub fn noop() -> Self {
+        Self {
+            payload: ENGINE_IO_NOOP_PAYLOAD.to_string(),
+            compress: false,
+        }
+    }
+}
+
+pub async fn handle_long_pool(
+    socket_io_list: &SocketIoList,
+    sid: &str,
+    poll_timeout: Duration,
+    compression: &MySocketIoCompressionOptions,
+    accept_encoding: Option<&str>,
+) -> Result<HttpOkResult, HttpFailResult> {
+    let socket_io = socket_io_list.get_by_socket_io_id(sid);
+
+    if socket_io.is_none() {
+        return Err(HttpFailResult {
+            write_telemetry: false,
+            content_type: WebContentType::Text,
+            status_code: 400,
+            content: format!("Socket.IO with id {} is not found", sid).into_bytes(),
+        });
+    }
+
+    let socket_io = socket_io.unwrap();
+
+    socket_io.update_incoming_activity();
+
+    if !socket_io.mark_connect_payload_sent() {
         return HttpOutput::Content {
             headers: None,
             content_type: Some(WebContentType::Text),
-            content: format!("SocketId with id {} is not founnd", sid).into_bytes(),
+            content: socket_io_utils::my_socket_io_messages::compile_connect_payload(sid),
         }
         .into_ok_result(true)
         .into();
     }
 
-    let web_socket = web_socket.unwrap();
+    let awaiter = socket_io.set_long_pooling_task().await;
 
-    let awaiter = web_socket.set_long_pooling_task().await;
+    let result = awaiter.get_result();
+    tokio::pin!(result);
 
-    let result = awaiter.get_result().await;
+    let result = match tokio::time::timeout(poll_timeout, &mut result).await {
+        Ok(result) => result,
+        Err(_) => {
+            if socket_io.cancel_long_pooling().await {
+                Ok(LongPoolPayload::noop())
+            } else {
+                // Writer has completed the LongPool right before the timeout
+                result.await
+            }
+        }
+    };
 
     match result {
-        Ok(result) => HttpOutput::as_text(result).into_ok_result(false).into(),
+        Ok(result) => {
+            let compressed = match accept_encoding {
+                Some(accept_encoding) if result.compress => {
+                    crate::compression::compress_http_payload(
+                        compression,
+                        accept_encoding,
+                        result.payload.as_bytes(),
+                    )
+                }
+                _ => None,
+            };
+
+            let mut headers = HashMap::new();
+
+            // Same url gives compressed or plain content depending on Accept-Encoding
+            if compression.http_compression {
+                headers.insert("Vary".to_string(), "Accept-Encoding".to_string());
+            }
+
+            let content = match compressed {
+                Some((content_encoding, content)) => {
+                    headers.insert("Content-Encoding".to_string(), content_encoding.to_string());
+                    content
+                }
+                None => result.payload.into_bytes(),
+            };
+
+            HttpOutput::Content {
+                headers: Some(headers),
+                content_type: Some(WebContentType::Text),
+                content,
+            }
+            .into_ok_result(false)
+            .into()
+        }
         Err(err) => Err(HttpFailResult {
             write_telemetry: false,
             content_type: WebContentType::Text,
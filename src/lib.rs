@@ -1,19 +1,69 @@
+mod ack_responder;
+mod admin;
+mod broadcast_ack;
+mod compression;
+mod disconnect_reason;
+mod dynamic_namespace;
+mod emit_options;
+mod engine_options;
+mod event_packet;
+mod handle_long_pool;
+mod metrics;
+mod metrics_middleware;
 mod middleware;
+mod msgpack_parser;
 mod my_socket_io;
 mod my_socket_io_connection;
 mod my_socket_io_connections_callbacks;
+mod my_socket_io_error;
+mod my_socket_io_event_handler;
+mod my_socket_io_middleware;
 
+mod namespace_router;
 mod namespaces;
+mod parser;
+mod pending_acks;
+mod pending_dispatch_guard;
 mod process_connect;
 mod process_disconnect;
+mod rate_limit;
+mod send_queue;
+mod sharded_map;
 mod socket_io_list;
 mod socket_io_livness_loop;
+mod socket_io_writer_loop;
+mod transport;
+mod users_index;
 mod web_socket_callbacks;
+mod web_socket_session;
+pub use ack_responder::MySocketIoAckResponder;
+pub use admin::{MySocketIoAdminAuth, MySocketIoAdminOptions};
+pub use broadcast_ack::*;
+pub use compression::MySocketIoCompressionOptions;
+pub use disconnect_reason::*;
+pub use dynamic_namespace::*;
+pub use emit_options::*;
+pub use engine_options::*;
+pub use event_packet::{MySocketIoProtocolError, MySocketIoProtocolErrorPolicy};
+use handle_long_pool::handle_long_pool;
+pub use metrics::*;
+pub use metrics_middleware::MySocketIoMetricsMiddleware;
 pub use middleware::*;
+pub use msgpack_parser::*;
 pub use my_socket_io::*;
 pub use my_socket_io_connection::*;
 pub use my_socket_io_connections_callbacks::*;
+pub use my_socket_io_error::*;
+pub use my_socket_io_event_handler::*;
+pub use my_socket_io_middleware::*;
+pub use namespace_router::*;
+pub use parser::{MySocketIoJsonParser, MySocketIoParser};
+pub use pending_acks::MySocketIoAckError;
 use process_connect::process_connect;
 use process_disconnect::process_disconnect;
-use socket_io_list::SocketIoList;
+pub use rate_limit::*;
+pub use send_queue::{MySocketIoSendQueueOptions, MySocketIoSlowConsumerPolicy};
+pub use socket_io_list::SocketIoList;
+pub use transport::*;
 pub use web_socket_callbacks::WebSocketCallbacks;
+use web_socket_session::WebSocketSession;
@@ -0,0 +1,623 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, RwLock,
+    },
+    time::Duration,
+};
+
+use crate::MySocketIoDisconnectReason;
+
+// Event names and dynamic namespaces come from clients, so we cap the amount of labels we keep
+const MAX_EVENT_LABELS: usize = 1000;
+const OTHER_EVENT_LABEL: &str = "_other";
+
+const ACK_LATENCY_BUCKETS_MS: [u64; 12] =
+    [1, 5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000];
+
+#[derive(Debug, Clone, Default)]
+pub struct MySocketIoHistogramSnapshot {
+    pub buckets: Vec<(u64, u64)>,
+    pub count: u64,
+    pub sum_ms: f64,
+}
+
+struct Histogram {
+    buckets: [u64; ACK_LATENCY_BUCKETS_MS.len()],
+    count: u64,
+    sum_ms: f64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            buckets: [0; ACK_LATENCY_BUCKETS_MS.len()],
+            count: 0,
+            sum_ms: 0.0,
+        }
+    }
+
+    fn observe(&mut self, value: Duration) {
+        let value_ms = value.as_secs_f64() * 1000.0;
+
+        for (i, bucket) in ACK_LATENCY_BUCKETS_MS.iter().enumerate() {
+            if value_ms <= *bucket as f64 {
+                self.buckets[i] += 1;
+            }
+        }
+
+        self.count += 1;
+        self.sum_ms += value_ms;
+    }
+
+    fn to_snapshot(&self) -> MySocketIoHistogramSnapshot {
+        MySocketIoHistogramSnapshot {
+            buckets: ACK_LATENCY_BUCKETS_MS
+                .iter()
+                .zip(self.buckets.iter())
+                .map(|(bucket, count)| (*bucket, *count))
+                .collect(),
+            count: self.count,
+            sum_ms: self.sum_ms,
+        }
+    }
+}
+
+pub struct MySocketIoMetrics {
+    handshakes: AtomicU64,
+    upgrades: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    rate_limited_events: AtomicU64,
+    rejected_connections: AtomicU64,
+    dropped_messages: AtomicU64,
+    coalesced_messages: AtomicU64,
+    volatile_dropped: Mutex<HashMap<String, u64>>,
+    disconnects: Mutex<HashMap<&'static str, u64>>,
+    events_in: EventCounters,
+    events_out: EventCounters,
+    handler_duration: Mutex<HashMap<String, Histogram>>,
+}
+
+impl MySocketIoMetrics {
+    pub fn new() -> Self {
+        Self {
+            handshakes: AtomicU64::new(0),
+            upgrades: AtomicU64::new(0),
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            rate_limited_events: AtomicU64::new(0),
+            rejected_connections: AtomicU64::new(0),
+            dropped_messages: AtomicU64::new(0),
+            coalesced_messages: AtomicU64::new(0),
+            volatile_dropped: Mutex::new(HashMap::new()),
+            disconnects: Mutex::new(HashMap::new()),
+            events_in: EventCounters::new(),
+            events_out: EventCounters::new(),
+            handler_duration: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn handshake(&self) {
+        self.handshakes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn upgrade(&self) {
+        self.upgrades.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn bytes_sent(&self, size: usize) {
+        self.bytes_sent.fetch_add(size as u64, Ordering::Relaxed);
+    }
+
+    pub fn bytes_received(&self, size: usize) {
+        self.bytes_received
+            .fetch_add(size as u64, Ordering::Relaxed);
+    }
+
+    pub fn rate_limited(&self) {
+        self.rate_limited_events.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_rejected(&self) {
+        self.rejected_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn message_dropped(&self) {
+        self.dropped_messages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn message_coalesced(&self) {
+        self.coalesced_messages.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn volatile_dropped(&self, nsp: &str) {
+        let mut write_access = self.volatile_dropped.lock().unwrap();
+        let nsp = get_label(&write_access, nsp);
+
+        if let Some(value) = write_access.get_mut(nsp) {
+            *value += 1;
+            return;
+        }
+
+        write_access.insert(nsp.to_string(), 1);
+    }
+
+    pub fn disconnected(&self, reason: MySocketIoDisconnectReason) {
+        let mut write_access = self.disconnects.lock().unwrap();
+        *write_access.entry(reason.as_str()).or_insert(0) += 1;
+    }
+
+    pub fn event_in(&self, nsp: &str, event_name: &str) {
+        self.events_in.inc(nsp, event_name);
+    }
+
+    pub fn event_out(&self, nsp: &str, event_name: &str) {
+        self.events_out.inc(nsp, event_name);
+    }
+
+    // Time from receiving an event to sending its ack. Not a client round trip
+    pub fn handler_duration(&self, nsp: &str, duration: Duration) {
+        let mut write_access = self.handler_duration.lock().unwrap();
+        let nsp = get_label(&write_access, nsp);
+
+        if !write_access.contains_key(nsp) {
+            write_access.insert(nsp.to_string(), Histogram::new());
+        }
+
+        write_access.get_mut(nsp).unwrap().observe(duration);
+    }
+
+    pub fn get_handshakes(&self) -> u64 {
+        self.handshakes.load(Ordering::Relaxed)
+    }
+
+    pub fn get_upgrades(&self) -> u64 {
+        self.upgrades.load(Ordering::Relaxed)
+    }
+
+    pub fn get_bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    pub fn get_bytes_received(&self) -> u64 {
+        self.bytes_received.load(Ordering::Relaxed)
+    }
+
+    pub fn get_rate_limited_events(&self) -> u64 {
+        self.rate_limited_events.load(Ordering::Relaxed)
+    }
+
+    pub fn get_rejected_connections(&self) -> u64 {
+        self.rejected_connections.load(Ordering::Relaxed)
+    }
+
+    pub fn get_dropped_messages(&self) -> u64 {
+        self.dropped_messages.load(Ordering::Relaxed)
+    }
+
+    pub fn get_coalesced_messages(&self) -> u64 {
+        self.coalesced_messages.load(Ordering::Relaxed)
+    }
+
+    pub fn get_volatile_dropped(&self) -> Vec<(String, u64)> {
+        let read_access = self.volatile_dropped.lock().unwrap();
+        read_access
+            .iter()
+            .map(|(nsp, count)| (nsp.clone(), *count))
+            .collect()
+    }
+
+    pub fn get_disconnects(&self) -> Vec<(String, u64)> {
+        let read_access = self.disconnects.lock().unwrap();
+        read_access
+            .iter()
+            .map(|(reason, count)| (reason.to_string(), *count))
+            .collect()
+    }
+
+    pub fn get_events_in(&self) -> Vec<MySocketIoEventCounter> {
+        self.events_in.get_all()
+    }
+
+    pub fn get_events_out(&self) -> Vec<MySocketIoEventCounter> {
+        self.events_out.get_all()
+    }
+
+    pub fn get_handler_duration(&self) -> Vec<(String, MySocketIoHistogramSnapshot)> {
+        let read_access = self.handler_duration.lock().unwrap();
+        read_access
+            .iter()
+            .map(|(nsp, histogram)| (nsp.clone(), histogram.to_snapshot()))
+            .collect()
+    }
+}
+
+impl Default for MySocketIoMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn get_label<'s, TValue>(items: &HashMap<String, TValue>, label: &'s str) -> &'s str {
+    if items.len() < MAX_EVENT_LABELS || items.contains_key(label) {
+        label
+    } else {
+        OTHER_EVENT_LABEL
+    }
+}
+
+#[derive(Default)]
+struct EventCountersInner {
+    items: HashMap<String, HashMap<String, AtomicU64>>,
+    labels: usize,
+}
+
+impl EventCountersInner {
+    fn get(&self, nsp: &str, event_name: &str) -> Option<&AtomicU64> {
+        self.items.get(nsp)?.get(event_name)
+    }
+}
+
+// Counted on every event, so known labels are incremented under the read lock without allocations.
+// Write lock is taken only to add a new label
+struct EventCounters {
+    items: RwLock<EventCountersInner>,
+}
+
+impl EventCounters {
+    fn new() -> Self {
+        Self {
+            items: RwLock::new(EventCountersInner::default()),
+        }
+    }
+
+    fn inc(&self, nsp: &str, event_name: &str) {
+        {
+            let read_access = self.items.read().unwrap();
+            let nsp = get_label(&read_access.items, nsp);
+
+            let counter = match read_access.get(nsp, event_name) {
+                Some(counter) => Some(counter),
+                None if read_access.labels >= MAX_EVENT_LABELS => {
+                    read_access.get(nsp, OTHER_EVENT_LABEL)
+                }
+                None => None,
+            };
+
+            if let Some(counter) = counter {
+                counter.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+
+        let mut write_access = self.items.write().unwrap();
+        let write_access = &mut *write_access;
+        let nsp = get_label(&write_access.items, nsp);
+
+        let event_name = if write_access.labels < MAX_EVENT_LABELS {
+            event_name
+        } else {
+            OTHER_EVENT_LABEL
+        };
+
+        let by_nsp = write_access.items.entry(nsp.to_string()).or_default();
+
+        if !by_nsp.contains_key(event_name) {
+            by_nsp.insert(event_name.to_string(), AtomicU64::new(0));
+            write_access.labels += 1;
+        }
+
+        by_nsp[event_name].fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn get_all(&self) -> Vec<MySocketIoEventCounter> {
+        let read_access = self.items.read().unwrap();
+        read_access
+            .items
+            .iter()
+            .flat_map(|(nsp, events)| {
+                events
+                    .iter()
+                    .map(move |(event_name, count)| MySocketIoEventCounter {
+                        nsp: nsp.clone(),
+                        event_name: event_name.clone(),
+                        count: count.load(Ordering::Relaxed),
+                    })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MySocketIoEventCounter {
+    pub nsp: String,
+    pub event_name: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct MySocketIoMetricsSnapshot {
+    pub web_socket_connections: usize,
+    pub long_polling_connections: usize,
+    // Counters only. Per second rates are derived on the Prometheus side: rate(socket_io_handshakes_total[1m])
+    pub handshakes: u64,
+    pub upgrades: u64,
+    pub disconnects: Vec<(String, u64)>,
+    pub events_in: Vec<MySocketIoEventCounter>,
+    pub events_out: Vec<MySocketIoEventCounter>,
+    pub handler_duration: Vec<(String, MySocketIoHistogramSnapshot)>,
+    pub pending_dispatches: usize,
+    pub send_queue_depth_total: usize,
+    pub send_queue_depth_max: usize,
+    pub dropped_messages: u64,
+    pub coalesced_messages: u64,
+    pub volatile_dropped: Vec<(String, u64)>,
+    pub protocol_errors: u64,
+    pub rate_limited_events: u64,
+    pub rejected_connections: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+impl MySocketIoMetricsSnapshot {
+    pub fn to_prometheus_text(&self) -> String {
+        let mut result = String::new();
+
+        write_header(&mut result, "socket_io_connections", "gauge");
+        result.push_str(
+            format!(
+                "socket_io_connections{{transport=\"websocket\"}} {}\n",
+                self.web_socket_connections
+            )
+            .as_str(),
+        );
+        result.push_str(
+            format!(
+                "socket_io_connections{{transport=\"polling\"}} {}\n",
+                self.long_polling_connections
+            )
+            .as_str(),
+        );
+
+        write_counter(&mut result, "socket_io_handshakes_total", self.handshakes);
+        write_counter(&mut result, "socket_io_upgrades_total", self.upgrades);
+
+        write_header(&mut result, "socket_io_disconnects_total", "counter");
+        for (reason, count) in &self.disconnects {
+            result.push_str(
+                format!(
+                    "socket_io_disconnects_total{{reason=\"{}\"}} {}\n",
+                    reason, count
+                )
+                .as_str(),
+            );
+        }
+
+        write_events(&mut result, "socket_io_events_in_total", &self.events_in);
+        write_events(&mut result, "socket_io_events_out_total", &self.events_out);
+
+        write_header(&mut result, "socket_io_handler_duration_ms", "histogram");
+        for (nsp, histogram) in &self.handler_duration {
+            let nsp = escape_label(nsp);
+            for (bucket, count) in &histogram.buckets {
+                result.push_str(
+                    format!(
+                        "socket_io_handler_duration_ms_bucket{{nsp=\"{}\",le=\"{}\"}} {}\n",
+                        nsp, bucket, count
+                    )
+                    .as_str(),
+                );
+            }
+            result.push_str(
+                format!(
+                    "socket_io_handler_duration_ms_bucket{{nsp=\"{}\",le=\"+Inf\"}} {}\n",
+                    nsp, histogram.count
+                )
+                .as_str(),
+            );
+            result.push_str(
+                format!(
+                    "socket_io_handler_duration_ms_sum{{nsp=\"{}\"}} {}\n",
+                    nsp, histogram.sum_ms
+                )
+                .as_str(),
+            );
+            result.push_str(
+                format!(
+                    "socket_io_handler_duration_ms_count{{nsp=\"{}\"}} {}\n",
+                    nsp, histogram.count
+                )
+                .as_str(),
+            );
+        }
+
+        write_header(&mut result, "socket_io_pending_dispatches", "gauge");
+        result.push_str(
+            format!("socket_io_pending_dispatches {}\n", self.pending_dispatches).as_str(),
+        );
+
+        write_header(&mut result, "socket_io_send_queue_depth", "gauge");
+        result.push_str(
+            format!(
+                "socket_io_send_queue_depth{{stat=\"total\"}} {}\n",
+                self.send_queue_depth_total
+            )
+            .as_str(),
+        );
+        result.push_str(
+            format!(
+                "socket_io_send_queue_depth{{stat=\"max\"}} {}\n",
+                self.send_queue_depth_max
+            )
+            .as_str(),
+        );
+
+        write_counter(
+            &mut result,
+            "socket_io_dropped_messages_total",
+            self.dropped_messages,
+        );
+        write_counter(
+            &mut result,
+            "socket_io_coalesced_messages_total",
+            self.coalesced_messages,
+        );
+
+        write_header(&mut result, "socket_io_volatile_dropped_total", "counter");
+        for (nsp, count) in &self.volatile_dropped {
+            result.push_str(
+                format!(
+                    "socket_io_volatile_dropped_total{{nsp=\"{}\"}} {}\n",
+                    escape_label(nsp),
+                    count
+                )
+                .as_str(),
+            );
+        }
+
+        write_counter(
+            &mut result,
+            "socket_io_protocol_errors_total",
+            self.protocol_errors,
+        );
+        write_counter(
+            &mut result,
+            "socket_io_rate_limited_events_total",
+            self.rate_limited_events,
+        );
+        write_counter(
+            &mut result,
+            "socket_io_rejected_connections_total",
+            self.rejected_connections,
+        );
+        write_counter(&mut result, "socket_io_bytes_sent_total", self.bytes_sent);
+        write_counter(
+            &mut result,
+            "socket_io_bytes_received_total",
+            self.bytes_received,
+        );
+
+        result
+    }
+}
+
+fn write_header(dest: &mut String, name: &str, metric_type: &str) {
+    dest.push_str(format!("# TYPE {} {}\n", name, metric_type).as_str());
+}
+
+fn write_counter(dest: &mut String, name: &str, value: u64) {
+    write_header(dest, name, "counter");
+    dest.push_str(format!("{} {}\n", name, value).as_str());
+}
+
+fn write_events(dest: &mut String, name: &str, events: &[MySocketIoEventCounter]) {
+    write_header(dest, name, "counter");
+    for event in events {
+        dest.push_str(
+            format!(
+                "{}{{nsp=\"{}\",event=\"{}\"}} {}\n",
+                name,
+                escape_label(event.nsp.as_str()),
+                escape_label(event.event_name.as_str()),
+                event.count
+            )
+            .as_str(),
+        );
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_counters() {
+        let counters = EventCounters::new();
+
+        counters.inc("/", "chat");
+        counters.inc("/", "chat");
+        counters.inc("/admin", "chat");
+
+        let mut result: Vec<_> = counters
+            .get_all()
+            .into_iter()
+            .map(|itm| (itm.nsp, itm.event_name, itm.count))
+            .collect();
+        result.sort();
+
+        assert_eq!(
+            result,
+            vec![
+                ("/".to_string(), "chat".to_string(), 2),
+                ("/admin".to_string(), "chat".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_event_labels_are_capped() {
+        let counters = EventCounters::new();
+
+        for i in 0..MAX_EVENT_LABELS + 10 {
+            counters.inc("/", format!("event-{}", i).as_str());
+        }
+
+        counters.inc("/", "event-0");
+
+        let result = counters.get_all();
+
+        assert_eq!(result.len(), MAX_EVENT_LABELS + 1);
+
+        let other = result
+            .iter()
+            .find(|itm| itm.event_name == OTHER_EVENT_LABEL)
+            .unwrap();
+        assert_eq!(other.count, 10);
+
+        let first = result
+            .iter()
+            .find(|itm| itm.event_name == "event-0")
+            .unwrap();
+        assert_eq!(first.count, 2);
+    }
+
+    #[test]
+    fn test_namespace_labels_are_capped() {
+        let metrics = MySocketIoMetrics::new();
+
+        for i in 0..MAX_EVENT_LABELS + 10 {
+            let nsp = format!("/tenant-{}", i);
+            metrics.handler_duration(nsp.as_str(), Duration::from_millis(1));
+            metrics.volatile_dropped(nsp.as_str());
+            metrics.event_in(nsp.as_str(), "chat");
+        }
+
+        let handler_duration = metrics.get_handler_duration();
+        assert_eq!(handler_duration.len(), MAX_EVENT_LABELS + 1);
+        let (_, other) = handler_duration
+            .iter()
+            .find(|(nsp, _)| nsp == OTHER_EVENT_LABEL)
+            .unwrap();
+        assert_eq!(other.count, 10);
+
+        let volatile_dropped = metrics.get_volatile_dropped();
+        assert_eq!(volatile_dropped.len(), MAX_EVENT_LABELS + 1);
+        assert!(volatile_dropped.contains(&(OTHER_EVENT_LABEL.to_string(), 10)));
+
+        let events_in = metrics.get_events_in();
+        let other = events_in
+            .iter()
+            .find(|itm| itm.nsp == OTHER_EVENT_LABEL)
+            .unwrap();
+        assert_eq!(other.count, 10);
+    }
+}
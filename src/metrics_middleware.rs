@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use my_http_server::{
+    HttpContext, HttpFailResult, HttpOkResult, HttpOutput, HttpServerMiddleware,
+    HttpServerRequestFlow, WebContentType,
+};
+
+use crate::{MySocketIoMetrics, MySocketIoMetricsSnapshot, SocketIoList, WebSocketCallbacks};
+
+pub(crate) async fn get_metrics_snapshot(
+    metrics: &Arc<MySocketIoMetrics>,
+    socket_io_list: &Arc<SocketIoList>,
+    web_socket_callback: &Arc<WebSocketCallbacks>,
+) -> MySocketIoMetricsSnapshot {
+    let (web_socket_connections, long_polling_connections) =
+        socket_io_list.get_count_by_transport();
+
+    let (send_queue_depth_total, send_queue_depth_max) = socket_io_list.get_send_queue_depths();
+
+    MySocketIoMetricsSnapshot {
+        web_socket_connections,
+        long_polling_connections,
+        handshakes: metrics.get_handshakes(),
+        upgrades: metrics.get_upgrades(),
+        disconnects: metrics.get_disconnects(),
+        events_in: metrics.get_events_in(),
+        events_out: metrics.get_events_out(),
+        handler_duration: metrics.get_handler_duration(),
+        pending_dispatches: web_socket_callback.get_pending_dispatches(),
+        send_queue_depth_total,
+        send_queue_depth_max,
+        dropped_messages: metrics.get_dropped_messages(),
+        coalesced_messages: metrics.get_coalesced_messages(),
+        volatile_dropped: metrics.get_volatile_dropped(),
+        protocol_errors: web_socket_callback.get_protocol_errors_count(),
+        rate_limited_events: metrics.get_rate_limited_events(),
+        rejected_connections: metrics.get_rejected_connections(),
+        bytes_sent: metrics.get_bytes_sent(),
+        bytes_received: metrics.get_bytes_received(),
+    }
+}
+
+// Serves Prometheus text exposition of the engine metrics. Can be registered on any my-http-server
+pub struct MySocketIoMetricsMiddleware {
+    pub path: String,
+    metrics: Arc<MySocketIoMetrics>,
+    socket_io_list: Arc<SocketIoList>,
+    web_socket_callback: Arc<WebSocketCallbacks>,
+}
+
+impl MySocketIoMetricsMiddleware {
+    pub(crate) fn new(
+        path: &str,
+        metrics: Arc<MySocketIoMetrics>,
+        socket_io_list: Arc<SocketIoList>,
+        web_socket_callback: Arc<WebSocketCallbacks>,
+    ) -> Self {
+        Self {
+            path: path.to_string(),
+            metrics,
+            socket_io_list,
+            web_socket_callback,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl HttpServerMiddleware for MySocketIoMetricsMiddleware {
+    async fn handle_request(
+        &self,
+        ctx: &mut HttpContext,
+        get_next: &mut HttpServerRequestFlow,
+    ) -> Result<HttpOkResult, HttpFailResult> {
+        if ctx.request.get_path() != self.path.as_str() {
+            return get_next.next(ctx).await;
+        }
+
+        let snapshot = get_metrics_snapshot(
+            &self.metrics,
+            &self.socket_io_list,
+            &self.web_socket_callback,
+        )
+        .await;
+
+        HttpOutput::Content {
+            headers: None,
+            content_type: Some(WebContentType::Text),
+            content: snapshot.to_prometheus_text().into_bytes(),
+        }
+        .into_ok_result(false)
+        .into()
+    }
+}
@@ -1,6 +1,12 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use hyper::Method;
+use hyper::{body::HttpBody, Method};
 use my_http_server::{
     HttpContext, HttpFailResult, HttpOkResult, HttpOutput, HttpServerMiddleware,
     HttpServerRequestFlow, RequestData, WebContentType,
@@ -9,10 +15,20 @@ use socket_io_utils::SocketIoSettings;
 use tokio::sync::Mutex;
 
 use crate::{
-    namespaces::SocketIoNameSpaces, MySocketIo, MySocketIoConnectionsCallbacks, SocketIoList,
-    WebSocketCallbacks,
+    namespaces::SocketIoNameSpaces, MySocketIo, MySocketIoAckError, MySocketIoBroadcastAckResult,
+    MySocketIoConnection, MySocketIoConnectionsCallbacks, MySocketIoDisconnectReason,
+    MySocketIoDynamicNamespace, MySocketIoEngineOptions, MySocketIoMetrics,
+    MySocketIoMetricsMiddleware, MySocketIoMetricsSnapshot, MySocketIoMiddleware, SocketIoList,
+    WebSocketCallbacks, WebSocketSession,
 };
 
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+enum PostBodyError {
+    TooLarge,
+    Failed(String),
+}
+
 pub struct MySocketIoEngineMiddleware {
     pub path_prefix: String,
     socket_id: Mutex<i64>,
@@ -21,6 +37,8 @@ pub struct MySocketIoEngineMiddleware {
     registered_sockets: Arc<SocketIoNameSpaces>,
     connections_callback: Arc<dyn MySocketIoConnectionsCallbacks + Send + Sync + 'static>,
     pub settings: Arc<SocketIoSettings>,
+    pub options: Arc<MySocketIoEngineOptions>,
+    metrics: Arc<MySocketIoMetrics>,
     disconnect_timeout: Duration,
 }
 
@@ -28,9 +46,18 @@ impl MySocketIoEngineMiddleware {
     pub fn new(
         connections_callback: Arc<dyn MySocketIoConnectionsCallbacks + Send + Sync + 'static>,
     ) -> Self {
+        Self::new_with_options(connections_callback, MySocketIoEngineOptions::default())
+    }
+
+    pub fn new_with_options(
+        connections_callback: Arc<dyn MySocketIoConnectionsCallbacks + Send + Sync + 'static>,
+        options: MySocketIoEngineOptions,
+    ) -> Self {
+        let options = Arc::new(options);
         let registered_sockets = Arc::new(SocketIoNameSpaces::new());
-        let socket_io_list = Arc::new(SocketIoList::new());
+        let socket_io_list = Arc::new(SocketIoList::new(options.user_id_data_key.as_str()));
         let settings = Arc::new(SocketIoSettings::default());
+        let metrics = Arc::new(MySocketIoMetrics::new());
         Self {
             socket_io_list: socket_io_list.clone(),
 
@@ -40,11 +67,18 @@ impl MySocketIoEngineMiddleware {
                 registered_sockets: registered_sockets.clone(),
                 connections_callback: connections_callback.clone(),
                 settings: settings.clone(),
+                options: options.clone(),
+                shutting_down: AtomicBool::new(false),
+                pending_dispatches: Arc::new(AtomicUsize::new(0)),
+                protocol_errors: AtomicU64::new(0),
+                metrics: metrics.clone(),
             }),
             socket_id: Mutex::new(0),
             registered_sockets,
             connections_callback,
             settings,
+            options,
+            metrics,
             disconnect_timeout: Duration::from_secs(60),
         }
     }
@@ -53,6 +87,240 @@ impl MySocketIoEngineMiddleware {
         self.registered_sockets.add(socket_io).await;
     }
 
+    pub async fn register_dynamic_namespace(&self, dynamic: MySocketIoDynamicNamespace) {
+        self.registered_sockets.add_dynamic(dynamic).await;
+    }
+
+    pub async fn register_middleware(
+        &self,
+        nsp: &str,
+        middleware: Arc<dyn MySocketIoMiddleware + Send + Sync + 'static>,
+    ) {
+        self.registered_sockets
+            .add_middleware(nsp, middleware)
+            .await;
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.web_socket_callback.is_shutting_down()
+    }
+
+    pub fn get_protocol_errors_count(&self) -> u64 {
+        self.web_socket_callback.get_protocol_errors_count()
+    }
+
+    pub async fn get_metrics_snapshot(&self) -> MySocketIoMetricsSnapshot {
+        crate::metrics_middleware::get_metrics_snapshot(
+            &self.metrics,
+            &self.socket_io_list,
+            &self.web_socket_callback,
+        )
+        .await
+    }
+
+    pub fn get_socket_io_list(&self) -> Arc<SocketIoList> {
+        self.socket_io_list.clone()
+    }
+
+    pub async fn get_connections(&self) -> Vec<Arc<MySocketIoConnection>> {
+        self.socket_io_list.get_all()
+    }
+
+    pub async fn disconnect_socket_io(&self, sid: &str) -> bool {
+        let socket_io = match self.socket_io_list.get_by_socket_io_id(sid) {
+            Some(socket_io) => socket_io,
+            None => return false,
+        };
+
+        self.disconnect_by_server(&socket_io).await;
+        true
+    }
+
+    pub fn set_user_id(&self, socket_io: &Arc<MySocketIoConnection>, user_id: &str) {
+        self.socket_io_list.set_user_id(socket_io, user_id);
+    }
+
+    // Emits to every connection of the user which has joined the namespace. Returns amount of connections
+    pub async fn emit_to_user(
+        &self,
+        user_id: &str,
+        nsp: &str,
+        event_name: &str,
+        data: &str,
+    ) -> usize {
+        let mut result = 0;
+
+        for socket_io in self.socket_io_list.get_by_user_id(user_id) {
+            if socket_io.has_namespace(nsp).await {
+                socket_io.emit(nsp, event_name, data).await;
+                result += 1;
+            }
+        }
+
+        result
+    }
+
+    // Emits to every connection of the namespace (or of the room of the namespace) and waits for all acks.
+    // Connections which did not answer within timeout are reported as timed out
+    pub async fn broadcast_with_ack(
+        &self,
+        nsp: &str,
+        room: Option<&str>,
+        event_name: &str,
+        data: &str,
+        timeout: Duration,
+    ) -> MySocketIoBroadcastAckResult {
+        let connections = match room {
+            Some(room) => self.socket_io_list.get_by_room(nsp, room).await,
+            None => self.socket_io_list.get_by_namespace(nsp).await,
+        };
+
+        let acks = connections.iter().map(|socket_io| async move {
+            let result = socket_io
+                .emit_with_ack(nsp, event_name, data, timeout)
+                .await;
+            (socket_io.id.clone(), result)
+        });
+
+        let mut result = MySocketIoBroadcastAckResult::default();
+
+        for (sid, ack) in futures::future::join_all(acks).await {
+            match ack {
+                Ok(ack_data) => {
+                    result.responses.insert(sid, ack_data);
+                }
+                Err(MySocketIoAckError::Timeout) => result.timed_out.push(sid),
+                Err(MySocketIoAckError::Disconnected) => result.disconnected.push(sid),
+            }
+        }
+
+        result
+    }
+
+    // Returns amount of disconnected connections
+    pub async fn disconnect_user(&self, user_id: &str) -> usize {
+        let connections = self.socket_io_list.get_by_user_id(user_id);
+
+        for socket_io in &connections {
+            self.disconnect_by_server(socket_io).await;
+        }
+
+        connections.len()
+    }
+
+    async fn disconnect_by_server(&self, socket_io: &Arc<MySocketIoConnection>) {
+        socket_io.send_disconnect_packets().await;
+
+        crate::process_disconnect(
+            &self.socket_io_list,
+            &self.registered_sockets,
+            socket_io,
+            &self.connections_callback,
+            MySocketIoDisconnectReason::ServerDisconnect,
+        )
+        .await;
+    }
+
+    pub fn create_metrics_middleware(&self, path: &str) -> MySocketIoMetricsMiddleware {
+        MySocketIoMetricsMiddleware::new(
+            path,
+            self.metrics.clone(),
+            self.socket_io_list.clone(),
+            self.web_socket_callback.clone(),
+        )
+    }
+
+    // Stops accepting new handshakes, tells every client it is disconnected,
+    // gives in-flight event handlers up to `grace` to send their acks and then drops all connections
+    pub async fn shutdown(&self, grace: Duration) {
+        self.web_socket_callback
+            .shutting_down
+            .store(true, Ordering::SeqCst);
+
+        for socket_io in self.socket_io_list.get_all() {
+            socket_io.send_disconnect_packets().await;
+        }
+
+        let started = tokio::time::Instant::now();
+
+        while self.web_socket_callback.get_pending_dispatches() > 0 {
+            if started.elapsed() >= grace {
+                break;
+            }
+
+            tokio::time::sleep(SHUTDOWN_POLL_INTERVAL).await;
+        }
+
+        for socket_io in self.socket_io_list.get_all() {
+            crate::process_disconnect(
+                &self.socket_io_list,
+                &self.registered_sockets,
+                &socket_io,
+                &self.connections_callback,
+                MySocketIoDisconnectReason::ServerShutdown,
+            )
+            .await;
+        }
+    }
+
+    async fn disconnect_on_payload_too_large(&self, sid: &str) {
+        if let Some(socket_io) = self.socket_io_list.get_by_socket_io_id(sid) {
+            crate::process_disconnect(
+                &self.socket_io_list,
+                &self.registered_sockets,
+                &socket_io,
+                &self.connections_callback,
+                MySocketIoDisconnectReason::PayloadTooLarge,
+            )
+            .await;
+        }
+    }
+
+    // Engine.IO payload the way a polling client POSTs it. Returns false if the connection is not found
+    pub async fn receive_packets(&self, sid: &str, payload: &str) -> bool {
+        let socket_io = match self.socket_io_list.get_by_socket_io_id(sid) {
+            Some(socket_io) => socket_io,
+            None => return false,
+        };
+
+        self.web_socket_callback
+            .on_polling_payload(&socket_io, payload)
+            .await;
+
+        true
+    }
+
+    async fn handle_post_request(
+        &self,
+        ctx: &mut HttpContext,
+        body: Vec<u8>,
+    ) -> Result<HttpOkResult, HttpFailResult> {
+        let sid = match get_sid(ctx) {
+            Some(sid) => sid,
+            None => return Err(bad_request("sid is required".to_string())),
+        };
+
+        let payload = match String::from_utf8(body) {
+            Ok(payload) => payload,
+            Err(_) => return Err(bad_request("Payload is not a valid UTF-8".to_string())),
+        };
+
+        if !self.receive_packets(sid.as_str(), payload.as_str()).await {
+            return Err(bad_request(format!(
+                "Socket.IO with id {} is not found",
+                sid
+            )));
+        }
+
+        HttpOutput::Content {
+            headers: None,
+            content_type: Some(WebContentType::Text),
+            content: "ok".to_string().into_bytes(),
+        }
+        .into_ok_result(true)
+        .into()
+    }
+
     async fn get_socket_id(&self) -> i64 {
         let mut socket_no = self.socket_id.lock().await;
         *socket_no += 1;
@@ -67,10 +335,25 @@ impl HttpServerMiddleware for MySocketIoEngineMiddleware {
         ctx: &mut HttpContext,
         get_next: &mut HttpServerRequestFlow,
     ) -> Result<HttpOkResult, HttpFailResult> {
+        if let Some(admin) = &self.options.admin {
+            if crate::admin::is_admin_path(ctx.request.get_path(), admin.path.as_str()) {
+                return crate::admin::handle_admin_request(self, admin, ctx).await;
+            }
+        }
+
         if ctx.request.get_path() != self.path_prefix.as_str() {
             return get_next.next(ctx).await;
         }
 
+        if self.is_shutting_down() && !has_sid(ctx) {
+            return Err(HttpFailResult {
+                write_telemetry: false,
+                content_type: WebContentType::Text,
+                status_code: 503,
+                content: "Socket.IO engine is shutting down".to_string().into_bytes(),
+            });
+        }
+
         if ctx
             .request
             .get_optional_header("sec-websocket-key")
@@ -80,7 +363,7 @@ impl HttpServerMiddleware for MySocketIoEngineMiddleware {
                 let id = self.get_socket_id().await;
                 return my_http_server_web_sockets::handle_web_socket_upgrade(
                     request,
-                    self.web_socket_callback.clone(),
+                    Arc::new(WebSocketSession::new(self.web_socket_callback.clone())),
                     id,
                     ctx.request.addr,
                     self.disconnect_timeout,
@@ -94,9 +377,12 @@ impl HttpServerMiddleware for MySocketIoEngineMiddleware {
         if ctx.request.method == Method::GET {
             if let Some(result) = handle_get_request(
                 ctx,
+                &self.web_socket_callback,
                 &self.connections_callback,
                 &self.socket_io_list,
                 &self.settings,
+                &self.options,
+                &self.metrics,
             )
             .await
             {
@@ -105,18 +391,100 @@ impl HttpServerMiddleware for MySocketIoEngineMiddleware {
         }
 
         if ctx.request.method == Method::POST {
-            return handle_post_request(ctx);
+            let max_size = self.options.max_http_buffer_size;
+
+            let body = match get_content_length(ctx) {
+                Some(content_length) if content_length > max_size => Err(PostBodyError::TooLarge),
+                _ => read_post_body(ctx, max_size).await,
+            };
+
+            return match body {
+                Ok(body) => self.handle_post_request(ctx, body).await,
+                Err(PostBodyError::TooLarge) => {
+                    if let Some(sid) = get_sid(ctx) {
+                        self.disconnect_on_payload_too_large(sid.as_str()).await;
+                    }
+
+                    Err(HttpFailResult {
+                        write_telemetry: false,
+                        content_type: WebContentType::Text,
+                        status_code: 413,
+                        content: "Payload too large".to_string().into_bytes(),
+                    })
+                }
+                Err(PostBodyError::Failed(reason)) => Err(bad_request(reason)),
+            };
         }
 
         get_next.next(ctx).await
     }
 }
 
+fn bad_request(reason: String) -> HttpFailResult {
+    HttpFailResult {
+        write_telemetry: false,
+        content_type: WebContentType::Text,
+        status_code: 400,
+        content: reason.into_bytes(),
+    }
+}
+
+fn get_sid(ctx: &mut HttpContext) -> Option<String> {
+    match ctx.request.get_query_string() {
+        Ok(query) => query.get_optional("sid").map(|sid| sid.value.to_string()),
+        Err(_) => None,
+    }
+}
+
+fn has_sid(ctx: &mut HttpContext) -> bool {
+    get_sid(ctx).is_some()
+}
+
+fn get_content_length(ctx: &mut HttpContext) -> Option<usize> {
+    ctx.request
+        .get_optional_header("content-length")?
+        .parse()
+        .ok()
+}
+
+// Content-Length is only a hint: chunked or header-less body is counted while it is read
+// and reading stops at the first chunk which goes over max_size
+async fn read_post_body(ctx: &mut HttpContext, max_size: usize) -> Result<Vec<u8>, PostBodyError> {
+    let request = match &mut ctx.request.req {
+        RequestData::AsRaw(request) => request,
+        _ => {
+            return Err(PostBodyError::Failed(
+                "Request body is already consumed".to_string(),
+            ))
+        }
+    };
+
+    let mut result = Vec::new();
+
+    while let Some(chunk) = request.body_mut().data().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(err) => return Err(PostBodyError::Failed(format!("{}", err))),
+        };
+
+        if result.len() + chunk.len() > max_size {
+            return Err(PostBodyError::TooLarge);
+        }
+
+        result.extend_from_slice(&chunk);
+    }
+
+    Ok(result)
+}
+
 async fn handle_get_request(
     ctx: &mut HttpContext,
+    web_socket_callback: &Arc<WebSocketCallbacks>,
     connections_callback: &Arc<dyn MySocketIoConnectionsCallbacks + Send + Sync + 'static>,
     socket_io_list: &Arc<SocketIoList>,
     settings: &Arc<SocketIoSettings>,
+    options: &Arc<MySocketIoEngineOptions>,
+    metrics: &Arc<MySocketIoMetrics>,
 ) -> Option<Result<HttpOkResult, HttpFailResult>> {
     let query = ctx.request.get_query_string();
 
@@ -129,18 +497,66 @@ async fn handle_get_request(
     let sid = query.get_optional("sid");
 
     if let Some(sid) = sid {
+        let sid = sid.value.to_string();
+        let accept_encoding = ctx
+            .request
+            .get_optional_header("accept-encoding")
+            .map(|value| value.to_string());
+
         return Some(
-            HttpOutput::Content {
-                headers: None,
-                content_type: Some(WebContentType::Text),
-                content: socket_io_utils::my_socket_io_messages::compile_connect_payload(sid.value),
-            }
-            .into_ok_result(true)
-            .into(),
+            crate::handle_long_pool(
+                socket_io_list,
+                sid.as_str(),
+                settings.get_ping_interval(),
+                &options.compression,
+                accept_encoding.as_deref(),
+            )
+            .await,
         );
     } else {
-        let (_, result) =
-            crate::process_connect(connections_callback, socket_io_list, settings, None).await;
+        if !web_socket_callback.try_reserve_ip_connection(&ctx.request.addr) {
+            return Some(Err(HttpFailResult {
+                write_telemetry: false,
+                content_type: WebContentType::Text,
+                status_code: 429,
+                content: "Too many connections from this address"
+                    .to_string()
+                    .into_bytes(),
+            }));
+        }
+
+        let (socket_io, result) = match crate::process_connect(
+            connections_callback,
+            socket_io_list,
+            settings,
+            options,
+            metrics,
+            None,
+            Some(ctx.request.addr),
+        )
+        .await
+        {
+            Ok(result) => result,
+            Err(err) => return Some(Err(err)),
+        };
+
+        // Open packet is the handshake response, queued messages go to the following polls
+        tokio::spawn(crate::socket_io_writer_loop::start(
+            connections_callback.clone(),
+            socket_io_list.clone(),
+            web_socket_callback.registered_sockets.clone(),
+            socket_io.clone(),
+        ));
+
+        // Polling client which is gone stops polling. Livness loop removes it and releases its ip slot
+        tokio::spawn(crate::socket_io_livness_loop::start(
+            connections_callback.clone(),
+            socket_io_list.clone(),
+            web_socket_callback.registered_sockets.clone(),
+            socket_io,
+            settings.get_ping_interval(),
+            settings.get_ping_timeout(),
+        ));
 
         let result = HttpOutput::Content {
             headers: None,
@@ -153,13 +569,3 @@ async fn handle_get_request(
         Some(result)
     }
 }
-
-fn handle_post_request(_ctx: &mut HttpContext) -> Result<HttpOkResult, HttpFailResult> {
-    return HttpOutput::Content {
-        headers: None,
-        content_type: Some(WebContentType::Text),
-        content: "ok".to_string().into_bytes(),
-    }
-    .into_ok_result(true)
-    .into();
-}
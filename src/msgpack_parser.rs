@@ -0,0 +1,196 @@
+use hyper_tungstenite::tungstenite::Message;
+use my_http_server_web_sockets::WebSocketMessage;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    parser::ENGINE_IO_MESSAGE_PREFIX, MySocketIoParser, MySocketIoProtocolError, DEFAULT_NAMESPACE,
+};
+
+// Socket.IO packet the way socket.io-msgpack-parser encodes it
+#[derive(Serialize, Deserialize)]
+struct MsgPackPacket {
+    #[serde(rename = "type")]
+    packet_type: u8,
+    nsp: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<i64>,
+}
+
+// Compatible with socket.io-msgpack-parser. Socket.IO packets go as binary WebSocket frames.
+// Supported on the WebSocket transport only
+pub struct MySocketIoMsgPackParser;
+
+impl MySocketIoParser for MySocketIoMsgPackParser {
+    fn get_name(&self) -> &'static str {
+        "msgpack"
+    }
+
+    fn encode(&self, payload: String) -> Message {
+        let packet = match payload.strip_prefix(ENGINE_IO_MESSAGE_PREFIX) {
+            Some(packet) => parse_text_packet(packet),
+            None => None,
+        };
+
+        let packet = match packet {
+            Some(packet) => packet,
+            None => return Message::Text(payload),
+        };
+
+        match rmp_serde::to_vec_named(&packet) {
+            Ok(result) => Message::Binary(result),
+            Err(err) => {
+                tracing::error!(error = %err, "Can not encode Socket.IO packet to MessagePack");
+                Message::Text(payload)
+            }
+        }
+    }
+
+    fn decode(&self, message: WebSocketMessage) -> Result<Option<String>, MySocketIoProtocolError> {
+        let payload = match message {
+            WebSocketMessage::String(value) => return Ok(Some(value)),
+            WebSocketMessage::Binary(payload) => payload,
+        };
+
+        match rmp_serde::from_slice::<MsgPackPacket>(payload.as_slice()) {
+            Ok(packet) => Ok(Some(compile_text_packet(&packet))),
+            Err(err) => Err(MySocketIoProtocolError::InvalidMsgPack(err.to_string())),
+        }
+    }
+}
+
+// Text encoding: <type>[<nsp>,][<id>][<json data>]
+fn parse_text_packet(packet: &str) -> Option<MsgPackPacket> {
+    let packet_type = packet.chars().next()?.to_digit(10)? as u8;
+    let mut rest = &packet[1..];
+
+    let nsp = if rest.starts_with('/') {
+        match rest.find(',') {
+            Some(index) => {
+                let nsp = &rest[..index];
+                rest = &rest[index + 1..];
+                nsp
+            }
+            None => {
+                let nsp = rest;
+                rest = "";
+                nsp
+            }
+        }
+    } else {
+        DEFAULT_NAMESPACE
+    };
+
+    let id_len = rest.bytes().take_while(|b| b.is_ascii_digit()).count();
+
+    let id = if id_len > 0 {
+        Some(rest[..id_len].parse().ok()?)
+    } else {
+        None
+    };
+
+    rest = &rest[id_len..];
+
+    let data = if rest.is_empty() {
+        None
+    } else {
+        Some(serde_json::from_str(rest).ok()?)
+    };
+
+    Some(MsgPackPacket {
+        packet_type,
+        nsp: nsp.to_string(),
+        data,
+        id,
+    })
+}
+
+fn compile_text_packet(packet: &MsgPackPacket) -> String {
+    let mut result = String::new();
+    result.push(ENGINE_IO_MESSAGE_PREFIX);
+    result.push_str(packet.packet_type.to_string().as_str());
+
+    if packet.nsp != DEFAULT_NAMESPACE {
+        result.push_str(packet.nsp.as_str());
+        result.push(',');
+    }
+
+    if let Some(id) = packet.id {
+        result.push_str(id.to_string().as_str());
+    }
+
+    if let Some(data) = &packet.data {
+        result.push_str(data.to_string().as_str());
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(payload: &str) -> String {
+        let message = MySocketIoMsgPackParser.encode(payload.to_string());
+        assert!(message.is_binary());
+
+        let binary = WebSocketMessage::Binary(message.into_data().to_vec());
+        MySocketIoMsgPackParser.decode(binary).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_event_round_trip() {
+        let payload = r#"42["event",{"a":1}]"#;
+        assert_eq!(payload, round_trip(payload));
+    }
+
+    #[test]
+    fn test_event_with_namespace_and_ack_id_round_trip() {
+        let payload = r#"42/admin,17["event",1,"two"]"#;
+        assert_eq!(payload, round_trip(payload));
+    }
+
+    #[test]
+    fn test_ack_round_trip() {
+        let payload = r#"43/admin,17["ok"]"#;
+        assert_eq!(payload, round_trip(payload));
+    }
+
+    #[test]
+    fn test_connect_without_data_round_trip() {
+        assert_eq!("40", round_trip("40"));
+        assert_eq!("40/admin,", round_trip("40/admin,"));
+    }
+
+    #[test]
+    fn test_engine_io_control_packet_stays_text() {
+        let message = MySocketIoMsgPackParser.encode("2".to_string());
+        assert_eq!("2", message.to_text().unwrap());
+    }
+
+    #[test]
+    fn test_packet_with_invalid_json_stays_text() {
+        let message = MySocketIoMsgPackParser.encode(r#"42["event""#.to_string());
+        assert_eq!(r#"42["event""#, message.to_text().unwrap());
+    }
+
+    #[test]
+    fn test_text_frame_is_passed_through() {
+        let result = MySocketIoMsgPackParser
+            .decode(WebSocketMessage::String("3".to_string()))
+            .unwrap();
+
+        assert_eq!(Some("3".to_string()), result);
+    }
+
+    #[test]
+    fn test_invalid_msgpack() {
+        let result = MySocketIoMsgPackParser.decode(WebSocketMessage::Binary(vec![0xc1]));
+
+        assert!(matches!(
+            result,
+            Err(MySocketIoProtocolError::InvalidMsgPack(_))
+        ));
+    }
+}
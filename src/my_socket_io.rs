@@ -1,5 +1,29 @@
+use crate::{MySocketIoAckResponder, MySocketIoError};
+
+#[derive(Debug, Clone)]
+pub struct MySocketIoEvent {
+    pub nsp: String,
+    pub event_name: String,
+    // First argument. "null" if the event has no arguments
+    pub event_data: String,
+    // Json of every argument in order: socket.emit("event", a, b) gives [a, b]
+    pub args: Vec<String>,
+    pub ack_requested: bool,
+}
+
+impl MySocketIoEvent {
+    pub fn get_payload_size(&self) -> usize {
+        let rest: usize = self.args.iter().skip(1).map(|arg| arg.len()).sum();
+        self.event_data.len() + rest
+    }
+}
+
 #[async_trait::async_trait]
 pub trait MySocketIo {
-    async fn on(&self, event_id: &str, message: &str) -> Option<String>;
+    async fn on(
+        &self,
+        event: &MySocketIoEvent,
+        ack: Option<MySocketIoAckResponder>,
+    ) -> Result<(), MySocketIoError>;
     fn get_nsp(&self) -> &str;
 }
@@ -1,19 +1,49 @@
-use std::sync::{atomic::AtomicBool, Arc};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
 
-use hyper_tungstenite::tungstenite::Message;
 use my_http_server_web_sockets::MyWebSocket;
 use rust_extensions::{
     date_time::{AtomicDateTimeAsMicroseconds, DateTimeAsMicroseconds},
-    TaskCompletion,
+    TaskCompletion, TaskCompletionAwaiter,
 };
-use tokio::sync::Mutex;
+use tokio::sync::{watch, Mutex};
 
 use socket_io_utils::my_socket_io_messages::*;
 
+use crate::{
+    handle_long_pool::LongPoolPayload,
+    pending_acks::PendingAcks,
+    send_queue::{EnqueueResult, OutgoingMessage, SendQueue},
+    users_index::UsersIndexBinding,
+    MySocketIoAckError, MySocketIoEmitOptions, MySocketIoError, MySocketIoMetrics,
+    MySocketIoParser, MySocketIoRateLimitState, MySocketIoSendQueueOptions,
+    MySocketIoSlowConsumerPolicy, MySocketIoTokenBucketSettings, MySocketIoTransport,
+};
+
+pub const DEFAULT_NAMESPACE: &str = "/";
+pub const ERROR_EVENT_NAME: &str = "error";
+pub(crate) const ENGINE_IO_PAYLOAD_SEPARATOR: char = '\u{1e}';
+// Slow client must not hold the disconnect for long
+const DISCONNECT_FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
 pub struct MySocketIoSingleThreaded {
     web_socket: Option<Arc<MyWebSocket>>,
-    long_pooling: Option<TaskCompletion<String, String>>,
+    // Probe WebSocket of a polling connection. Becomes web_socket once the client sends upgrade packet
+    upgrading_web_socket: Option<Arc<MyWebSocket>>,
+    polling_paused: bool,
+    long_pooling: Option<TaskCompletion<LongPoolPayload, String>>,
     updgraded_to_websocket: bool,
+    namespaces: Vec<String>,
+    rooms: Vec<(String, String)>,
+}
+
+struct MySocketIoConnectionData {
+    values: HashMap<String, String>,
+    users_index: Option<UsersIndexBinding>,
 }
 
 pub struct MySocketIoConnection {
@@ -21,37 +51,135 @@ pub struct MySocketIoConnection {
     pub id: String,
     pub created: DateTimeAsMicroseconds,
     pub last_incoming_moment: AtomicDateTimeAsMicroseconds,
+    pub remote_addr: Option<SocketAddr>,
+    pub span: tracing::Span,
     connected: AtomicBool,
     has_web_socket: AtomicBool,
+    disconnect_sender: watch::Sender<bool>,
+    metrics: Arc<MySocketIoMetrics>,
+    rate_limit_state: std::sync::Mutex<MySocketIoRateLimitState>,
+    send_queue: SendQueue,
+    slow_consumer: AtomicBool,
+    parser: Arc<dyn MySocketIoParser + Send + Sync + 'static>,
+    connect_payload_sent: AtomicBool,
+    data: std::sync::Mutex<MySocketIoConnectionData>,
+    pending_acks: PendingAcks,
 }
 
 impl MySocketIoConnection {
-    pub fn new(id: String, web_socket: Option<Arc<MyWebSocket>>) -> Self {
+    pub fn new(
+        id: String,
+        web_socket: Option<Arc<MyWebSocket>>,
+        remote_addr: Option<SocketAddr>,
+        send_queue_options: MySocketIoSendQueueOptions,
+        parser: Arc<dyn MySocketIoParser + Send + Sync + 'static>,
+        metrics: Arc<MySocketIoMetrics>,
+    ) -> Self {
         let has_web_socket = web_socket.is_some();
+
+        let span = tracing::info_span!(
+            "socket_io_connection",
+            sid = id.as_str(),
+            ws_id = tracing::field::Empty,
+            remote_addr = ?remote_addr,
+            transport = if has_web_socket { "websocket" } else { "polling" },
+        );
+
+        if let Some(web_socket) = &web_socket {
+            span.record("ws_id", web_socket.id);
+        }
+
+        let (disconnect_sender, _) = watch::channel(false);
         Self {
             single_threaded: Mutex::new(MySocketIoSingleThreaded {
                 web_socket,
+                upgrading_web_socket: None,
+                polling_paused: false,
                 long_pooling: None,
                 updgraded_to_websocket: false,
+                namespaces: Vec::new(),
+                rooms: Vec::new(),
             }),
             id,
             created: DateTimeAsMicroseconds::now(),
             last_incoming_moment: AtomicDateTimeAsMicroseconds::now(),
+            remote_addr,
+            span,
             connected: AtomicBool::new(true),
             has_web_socket: AtomicBool::new(has_web_socket),
+            disconnect_sender,
+            metrics,
+            rate_limit_state: std::sync::Mutex::new(MySocketIoRateLimitState::default()),
+            send_queue: SendQueue::new(send_queue_options),
+            slow_consumer: AtomicBool::new(false),
+            parser,
+            connect_payload_sent: AtomicBool::new(false),
+            data: std::sync::Mutex::new(MySocketIoConnectionData {
+                values: HashMap::new(),
+                users_index: None,
+            }),
+            pending_acks: PendingAcks::new(),
         }
     }
 
-    pub async fn upgrade_to_websocket(&self) {
+    // Upgrade packet is accepted only from the probe WebSocket after the probe pong.
+    // Returns false for an upgrade without probe, from another socket or a repeated one
+    pub async fn upgrade_to_websocket(&self, web_socket_id: i64) -> bool {
         let mut write_access = self.single_threaded.lock().await;
 
+        match &write_access.upgrading_web_socket {
+            Some(web_socket) if web_socket.id == web_socket_id && write_access.polling_paused => {}
+            _ => return false,
+        }
+
+        if let Some(web_socket) = write_access.upgrading_web_socket.take() {
+            self.span.record("ws_id", web_socket.id);
+            write_access.web_socket = Some(web_socket);
+        }
+
         write_access.updgraded_to_websocket = true;
+        write_access.polling_paused = false;
         if let Some(mut removed) = write_access.long_pooling.take() {
-            removed.set_error(MySocketIoMessage::Disconnect.as_str().to_string());
+            removed.set_ok(LongPoolPayload::noop());
         }
 
         self.has_web_socket
             .store(true, std::sync::atomic::Ordering::SeqCst);
+
+        self.span.record("transport", "websocket");
+        tracing::info!(parent: &self.span, "Socket.IO connection upgraded to websocket");
+
+        self.send_queue.wake_up();
+        true
+    }
+
+    // Client has got the probe pong. Outstanding and further polls are answered with NOOP
+    // so the client can send the upgrade packet. Queued messages wait for the WebSocket
+    pub(crate) async fn pause_polling(&self) {
+        let mut write_access = self.single_threaded.lock().await;
+
+        if write_access.upgrading_web_socket.is_none() {
+            return;
+        }
+
+        write_access.polling_paused = true;
+        if let Some(mut long_pooling) = write_access.long_pooling.take() {
+            long_pooling.set_ok(LongPoolPayload::noop());
+        }
+    }
+
+    // Upgrade did not happen. Returns the probe WebSocket to close; connection stays on polling
+    pub(crate) async fn cancel_upgrade(&self, web_socket_id: i64) -> Option<Arc<MyWebSocket>> {
+        let mut write_access = self.single_threaded.lock().await;
+
+        match &write_access.upgrading_web_socket {
+            Some(web_socket) if web_socket.id == web_socket_id => {}
+            _ => return None,
+        }
+
+        write_access.polling_paused = false;
+        self.send_queue.wake_up();
+        write_access.upgrading_web_socket.take()
     }
 
     pub fn in_web_socket_model(&self) -> bool {
@@ -59,6 +187,64 @@ impl MySocketIoConnection {
             .load(std::sync::atomic::Ordering::Relaxed)
     }
 
+    pub fn get_transport(&self) -> MySocketIoTransport {
+        if self.in_web_socket_model() {
+            MySocketIoTransport::WebSocket
+        } else {
+            MySocketIoTransport::Polling
+        }
+    }
+
+    // Application data attached to the connection (user id, tenant, etc).
+    // Writes of the user id key update the users index under the same lock
+    pub fn set_data(&self, key: &str, value: String) {
+        let mut write_access = self.data.lock().unwrap();
+        write_access.values.insert(key.to_string(), value);
+
+        if let Some(users_index) = &write_access.users_index {
+            users_index.update(self.id.as_str(), key, write_access.values.get(key));
+        }
+    }
+
+    pub fn get_data(&self, key: &str) -> Option<String> {
+        let read_access = self.data.lock().unwrap();
+        read_access.values.get(key).cloned()
+    }
+
+    pub fn remove_data(&self, key: &str) -> Option<String> {
+        let mut write_access = self.data.lock().unwrap();
+        let result = write_access.values.remove(key);
+
+        if let Some(users_index) = &write_access.users_index {
+            users_index.update(self.id.as_str(), key, None);
+        }
+
+        result
+    }
+
+    pub fn get_all_data(&self) -> HashMap<String, String> {
+        let read_access = self.data.lock().unwrap();
+        read_access.values.clone()
+    }
+
+    // Data set before the connection is added to the list (connected callback) goes to the index here
+    pub(crate) fn bind_users_index(&self, users_index: UsersIndexBinding) {
+        let mut write_access = self.data.lock().unwrap();
+
+        let key = users_index.user_id_data_key.as_str();
+        users_index.update(self.id.as_str(), key, write_access.values.get(key));
+
+        write_access.users_index = Some(users_index);
+    }
+
+    pub(crate) fn unbind_users_index(&self) {
+        let mut write_access = self.data.lock().unwrap();
+
+        if let Some(users_index) = write_access.users_index.take() {
+            users_index.remove(self.id.as_str());
+        }
+    }
+
     pub async fn has_web_socket(&self, web_socket_id: i64) -> bool {
         let read_access = self.single_threaded.lock().await;
         if let Some(web_socket) = &read_access.web_socket {
@@ -79,30 +265,387 @@ impl MySocketIoConnection {
     }
 
     pub async fn send_message(&self, message: &MySocketIoMessage) {
-        let web_socket = {
-            let read_access = self.single_threaded.lock().await;
-            read_access.web_socket.clone()
-        };
+        self.send_text(message.as_str().to_string());
+    }
+
+    // Control packets bypass the slow consumer policy, so a slow client still gets pings and acks
+    fn send_text(&self, payload: String) {
+        self.send_queue.enqueue_control(OutgoingMessage {
+            payload,
+            coalesce_key: None,
+            compress: true,
+        });
+    }
+
+    fn enqueue(&self, message: OutgoingMessage) {
+        let result = self.send_queue.enqueue(message);
+        self.handle_enqueue_result(result);
+    }
+
+    fn handle_enqueue_result(&self, result: EnqueueResult) {
+        match result {
+            EnqueueResult::Queued => {}
+            EnqueueResult::Coalesced => self.metrics.message_coalesced(),
+            EnqueueResult::Dropped => self.metrics.message_dropped(),
+            EnqueueResult::SlowConsumer => {
+                self.metrics.message_dropped();
+                self.slow_consumer
+                    .store(true, std::sync::atomic::Ordering::SeqCst);
+            }
+        }
+    }
+
+    // Not writable while there is no WebSocket, upgrade is in progress or the send queue is full
+    pub fn is_writable(&self) -> bool {
+        self.in_web_socket_model() && !self.send_queue.is_over_high_water_mark()
+    }
+
+    pub fn get_send_queue_len(&self) -> usize {
+        self.send_queue.len()
+    }
+
+    pub fn is_slow_consumer(&self) -> bool {
+        self.slow_consumer
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    pub(crate) async fn wait_for_outgoing_messages(&self) {
+        self.send_queue.wait().await;
+    }
 
-        if let Some(web_socket) = web_socket {
+    // Writes queued messages to the WebSocket or completes the pending LongPool with all of them.
+    // Messages stay in the queue while there is neither
+    pub(crate) async fn flush_send_queue(&self) {
+        if let Some(web_socket) = self.get_web_socket().await {
+            self.write_send_queue(&web_socket).await;
+            return;
+        }
+
+        let mut write_access = self.single_threaded.lock().await;
+
+        if write_access.long_pooling.is_none() {
+            return;
+        }
+
+        if let Some(payload) = self.take_long_pool_payload() {
+            if let Some(mut long_pooling) = write_access.long_pooling.take() {
+                long_pooling.set_ok(payload);
+            }
+        }
+    }
+
+    async fn write_send_queue(&self, web_socket: &MyWebSocket) {
+        while let Some(message) = self.send_queue.dequeue() {
+            self.metrics.bytes_sent(message.payload.len());
             web_socket
-                .send_message(Message::Text(message.as_str().to_string()))
+                .send_message(self.parser.encode(message.payload))
                 .await;
         }
     }
 
-    pub async fn add_web_socket(&self, web_socket: Arc<MyWebSocket>) {
-        let new_id = web_socket.id;
+    // All queued messages as one polling payload. None if the queue is empty
+    fn take_long_pool_payload(&self) -> Option<LongPoolPayload> {
+        if self.send_queue.len() == 0 {
+            return None;
+        }
+
+        let mut payload = String::new();
+        let mut compress = false;
+        while let Some(message) = self.send_queue.dequeue() {
+            if !payload.is_empty() {
+                payload.push(ENGINE_IO_PAYLOAD_SEPARATOR);
+            }
+            payload.push_str(message.payload.as_str());
+            compress |= message.compress;
+        }
+
+        self.metrics.bytes_sent(payload.len());
+
+        Some(LongPoolPayload { payload, compress })
+    }
+
+    pub(crate) async fn set_long_pooling_task(
+        &self,
+    ) -> TaskCompletionAwaiter<LongPoolPayload, String> {
+        let mut task_completion = TaskCompletion::new();
+        let awaiter = task_completion.get_awaiter();
+
         let mut write_access = self.single_threaded.lock().await;
 
-        if let Some(old_websocket) = write_access.web_socket.replace(web_socket) {
-            old_websocket
-                .send_message(hyper_tungstenite::tungstenite::Message::Text(format!(
-                    "SocketIO WebSocket {} has been kicked by Websocket {} ",
-                    old_websocket.id, new_id
-                )))
-                .await;
+        if write_access.polling_paused {
+            task_completion.set_ok(LongPoolPayload::noop());
+            return awaiter;
         }
+
+        if let Some(mut previous) = write_access.long_pooling.replace(task_completion) {
+            previous.set_error("LongPool is replaced by a newer one".to_string());
+        }
+
+        self.send_queue.wake_up();
+        awaiter
+    }
+
+    // Returns false if the LongPool has already been completed with messages
+    pub async fn cancel_long_pooling(&self) -> bool {
+        let mut write_access = self.single_threaded.lock().await;
+        match write_access.long_pooling.take() {
+            Some(mut long_pooling) => {
+                long_pooling.set_error("LongPool is timed out".to_string());
+                true
+            }
+            None => false,
+        }
+    }
+
+    // First poll after the handshake gets the connect payload
+    pub(crate) fn mark_connect_payload_sent(&self) -> bool {
+        self.connect_payload_sent
+            .swap(true, std::sync::atomic::Ordering::SeqCst)
+    }
+
+    pub async fn emit(&self, nsp: &str, event_name: &str, data: &str) {
+        self.emit_with_options(nsp, event_name, data, &MySocketIoEmitOptions::default())
+            .await;
+    }
+
+    pub async fn emit_with_options(
+        &self,
+        nsp: &str,
+        event_name: &str,
+        data: &str,
+        options: &MySocketIoEmitOptions,
+    ) {
+        if options.volatile && !self.is_writable() {
+            self.metrics.volatile_dropped(nsp);
+            return;
+        }
+
+        let payload = compile_event_payload(nsp, event_name, data, None);
+
+        let coalesce_key = if self.send_queue.get_slow_consumer_policy()
+            == MySocketIoSlowConsumerPolicy::Coalesce
+        {
+            Some(format!("{}:{}", nsp, event_name))
+        } else {
+            None
+        };
+
+        self.metrics.event_out(nsp, event_name);
+        self.enqueue(OutgoingMessage {
+            payload,
+            coalesce_key,
+            compress: options.compress,
+        });
+    }
+
+    // For high frequency streams (prices, positions): a message with the same nsp, event and key
+    // which is queued but not sent yet is replaced, so a slow client gets only the latest value
+    pub async fn emit_latest(&self, nsp: &str, event_name: &str, key: &str, data: &str) {
+        let payload = compile_event_payload(nsp, event_name, data, None);
+
+        self.metrics.event_out(nsp, event_name);
+
+        let result = self.send_queue.enqueue_latest(OutgoingMessage {
+            payload,
+            coalesce_key: Some(format!("{}\u{0}{}\u{0}{}", nsp, event_name, key)),
+            compress: true,
+        });
+
+        self.handle_enqueue_result(result);
+    }
+
+    // Server to client request. Result is the json array of arguments the client has acknowledged with
+    pub async fn emit_with_ack(
+        &self,
+        nsp: &str,
+        event_name: &str,
+        data: &str,
+        timeout: Duration,
+    ) -> Result<String, MySocketIoAckError> {
+        let (id, receiver) = self.pending_acks.register();
+
+        // Disconnect could have cancelled pending acks before this one is registered
+        if !self.is_connected() {
+            self.pending_acks.remove(id);
+            return Err(MySocketIoAckError::Disconnected);
+        }
+
+        let payload = compile_event_payload(nsp, event_name, data, Some(id));
+
+        self.metrics.event_out(nsp, event_name);
+        self.send_queue.enqueue_control(OutgoingMessage {
+            payload,
+            coalesce_key: None,
+            compress: true,
+        });
+
+        match tokio::time::timeout(timeout, receiver).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(MySocketIoAckError::Disconnected),
+            Err(_) => {
+                self.pending_acks.remove(id);
+                Err(MySocketIoAckError::Timeout)
+            }
+        }
+    }
+
+    pub(crate) fn resolve_ack(&self, id: i64, data: String) -> bool {
+        self.pending_acks.resolve(id, data)
+    }
+
+    pub fn get_pending_acks_count(&self) -> usize {
+        self.pending_acks.len()
+    }
+
+    pub fn try_consume_rate_limit(
+        &self,
+        settings: &MySocketIoTokenBucketSettings,
+        event_name: Option<&str>,
+    ) -> bool {
+        let mut write_access = self.rate_limit_state.lock().unwrap();
+        match event_name {
+            Some(event_name) => write_access.try_consume_event(event_name, settings),
+            None => write_access.try_consume_connection(settings),
+        }
+    }
+
+    pub(crate) fn get_metrics(&self) -> &Arc<MySocketIoMetrics> {
+        &self.metrics
+    }
+
+    pub async fn send_error(&self, nsp: Option<String>, id: Option<i64>, err: &MySocketIoError) {
+        let message = if id.is_some() {
+            MySocketIoMessage::Ack(MySocketIoTextPayload {
+                nsp,
+                data: format!("[{{\"error\":{}}}]", err.to_json()),
+                id,
+            })
+        } else {
+            MySocketIoMessage::Message(MySocketIoTextPayload {
+                nsp,
+                data: format!("[\"{}\",{}]", ERROR_EVENT_NAME, err.to_json()),
+                id: None,
+            })
+        };
+
+        self.send_message(&message).await;
+    }
+
+    pub async fn send_connect_error(&self, nsp: &str, err: &MySocketIoError) {
+        let payload = if nsp == DEFAULT_NAMESPACE {
+            format!("44{}", err.to_json())
+        } else {
+            format!("44{},{}", nsp, err.to_json())
+        };
+
+        self.send_text(payload);
+    }
+
+    pub async fn add_namespace(&self, nsp: &str) {
+        let mut write_access = self.single_threaded.lock().await;
+        if !write_access.namespaces.iter().any(|itm| itm == nsp) {
+            write_access.namespaces.push(nsp.to_string());
+        }
+    }
+
+    // Rooms of the namespace are left too. Returns false if the namespace is not joined
+    pub async fn remove_namespace(&self, nsp: &str) -> bool {
+        let mut write_access = self.single_threaded.lock().await;
+
+        let len = write_access.namespaces.len();
+        write_access.namespaces.retain(|itm| itm != nsp);
+
+        if write_access.namespaces.len() == len {
+            return false;
+        }
+
+        write_access.rooms.retain(|(itm_nsp, _)| itm_nsp != nsp);
+        true
+    }
+
+    pub async fn has_namespace(&self, nsp: &str) -> bool {
+        let read_access = self.single_threaded.lock().await;
+        read_access.namespaces.iter().any(|itm| itm == nsp)
+    }
+
+    pub async fn get_namespaces(&self) -> Vec<String> {
+        let read_access = self.single_threaded.lock().await;
+        read_access.namespaces.clone()
+    }
+
+    pub async fn join_room(&self, nsp: &str, room: &str) {
+        let mut write_access = self.single_threaded.lock().await;
+        if !write_access
+            .rooms
+            .iter()
+            .any(|(itm_nsp, itm_room)| itm_nsp == nsp && itm_room == room)
+        {
+            write_access.rooms.push((nsp.to_string(), room.to_string()));
+        }
+    }
+
+    pub async fn leave_room(&self, nsp: &str, room: &str) {
+        let mut write_access = self.single_threaded.lock().await;
+        write_access
+            .rooms
+            .retain(|(itm_nsp, itm_room)| !(itm_nsp == nsp && itm_room == room));
+    }
+
+    pub async fn is_in_room(&self, nsp: &str, room: &str) -> bool {
+        let read_access = self.single_threaded.lock().await;
+        read_access
+            .rooms
+            .iter()
+            .any(|(itm_nsp, itm_room)| itm_nsp == nsp && itm_room == room)
+    }
+
+    pub async fn get_rooms(&self, nsp: &str) -> Vec<String> {
+        let read_access = self.single_threaded.lock().await;
+        read_access
+            .rooms
+            .iter()
+            .filter(|(itm_nsp, _)| itm_nsp == nsp)
+            .map(|(_, room)| room.clone())
+            .collect()
+    }
+
+    pub async fn send_disconnect_packets(&self) {
+        let namespaces = self.get_namespaces().await;
+
+        if namespaces.is_empty() {
+            self.send_message(&MySocketIoMessage::Disconnect).await;
+            return;
+        }
+
+        for nsp in namespaces {
+            if nsp == DEFAULT_NAMESPACE {
+                self.send_message(&MySocketIoMessage::Disconnect).await;
+            } else {
+                self.send_text(format!("41{},", nsp));
+            }
+        }
+    }
+
+    pub fn subscribe_to_disconnect(&self) -> watch::Receiver<bool> {
+        self.disconnect_sender.subscribe()
+    }
+
+    // Returns replaced WebSocket. Caller is responsible to close it
+    pub async fn add_web_socket(&self, web_socket: Arc<MyWebSocket>) -> Option<Arc<MyWebSocket>> {
+        let mut write_access = self.single_threaded.lock().await;
+
+        if !self.in_web_socket_model() {
+            // New probe socket has to send its own probe before the upgrade
+            write_access.polling_paused = false;
+            self.send_queue.wake_up();
+            return write_access.upgrading_web_socket.replace(web_socket);
+        }
+
+        self.span.record("ws_id", web_socket.id);
+        let result = write_access.web_socket.replace(web_socket);
+
+        self.send_queue.wake_up();
+        result
     }
 
     pub async fn disconnect(&self) -> Option<Arc<MyWebSocket>> {
@@ -111,15 +654,36 @@ impl MySocketIoConnection {
         self.connected
             .store(false, std::sync::atomic::Ordering::SeqCst);
 
+        self.disconnect_sender.send_replace(true);
+        self.send_queue.wake_up();
+        self.pending_acks.cancel_all();
+
         let mut result = None;
 
+        // Packets queued by send_disconnect_packets reach the client before the transport is closed
         if let Some(web_socket) = write_access.web_socket.take() {
+            let flush = self.write_send_queue(&web_socket);
+            if tokio::time::timeout(DISCONNECT_FLUSH_TIMEOUT, flush)
+                .await
+                .is_err()
+            {
+                tracing::debug!(parent: &self.span, "Socket.IO send queue is not flushed on disconnect");
+            }
+
             web_socket.disconnect().await;
             result = Some(web_socket);
         }
 
+        if let Some(web_socket) = write_access.upgrading_web_socket.take() {
+            web_socket.disconnect().await;
+        }
+
         if let Some(mut long_pooling) = write_access.long_pooling.take() {
-            long_pooling.set_error(format!("Canceling this LongPool since we disconnect it."));
+            match self.take_long_pool_payload() {
+                Some(payload) => long_pooling.set_ok(payload),
+                None => long_pooling
+                    .set_error("Canceling this LongPool since we disconnect it.".to_string()),
+            }
         }
 
         result
@@ -129,3 +693,24 @@ impl MySocketIoConnection {
         self.connected.load(std::sync::atomic::Ordering::Relaxed)
     }
 }
+
+fn compile_event_payload(nsp: &str, event_name: &str, data: &str, id: Option<i64>) -> String {
+    let mut payload = String::new();
+    payload.push('[');
+    crate::my_socket_io_error::push_json_string(&mut payload, event_name);
+    payload.push(',');
+    payload.push_str(data);
+    payload.push(']');
+
+    let message = MySocketIoMessage::Message(MySocketIoTextPayload {
+        nsp: if nsp == DEFAULT_NAMESPACE {
+            None
+        } else {
+            Some(nsp.to_string())
+        },
+        data: payload,
+        id,
+    });
+
+    message.as_str().to_string()
+}
@@ -0,0 +1,52 @@
+#[derive(Debug, Clone)]
+pub struct MySocketIoError {
+    pub message: String,
+    pub data: Option<String>,
+}
+
+impl MySocketIoError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn with_data(message: impl Into<String>, data: String) -> Self {
+        Self {
+            message: message.into(),
+            data: Some(data),
+        }
+    }
+
+    // Same shape Socket.IO client uses for connect_error: {"message":"...","data":...}
+    pub fn to_json(&self) -> String {
+        let mut result = String::new();
+        result.push_str("{\"message\":");
+        push_json_string(&mut result, self.message.as_str());
+
+        if let Some(data) = &self.data {
+            result.push_str(",\"data\":");
+            result.push_str(data);
+        }
+
+        result.push('}');
+        result
+    }
+}
+
+pub(crate) fn push_json_string(dest: &mut String, value: &str) {
+    dest.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => dest.push_str("\\\""),
+            '\\' => dest.push_str("\\\\"),
+            '\n' => dest.push_str("\\n"),
+            '\r' => dest.push_str("\\r"),
+            '\t' => dest.push_str("\\t"),
+            c if (c as u32) < 0x20 => dest.push_str(format!("\\u{:04x}", c as u32).as_str()),
+            c => dest.push(c),
+        }
+    }
+    dest.push('"');
+}
@@ -0,0 +1,127 @@
+use std::{future::Future, marker::PhantomData};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{MySocketIoAckResponder, MySocketIoError, MySocketIoEvent};
+
+pub const INVALID_PAYLOAD_ERROR: &str = "Invalid payload";
+
+#[async_trait::async_trait]
+pub trait MySocketIoEventHandler {
+    async fn handle(
+        &self,
+        event: &MySocketIoEvent,
+        ack: Option<MySocketIoAckResponder>,
+    ) -> Result<(), MySocketIoError>;
+}
+
+// Async fn of a typed event: Fn(TPayload) -> Future<Output = Result<TAck, MySocketIoError>>.
+// Future type is associated, so only payload and ack types are left for turbofish: on::<MyPayload, MyAck>
+pub trait MySocketIoTypedHandlerFn<TPayload, TAck>:
+    Fn(TPayload) -> <Self as MySocketIoTypedHandlerFn<TPayload, TAck>>::Future + Send + Sync + 'static
+{
+    type Future: Future<Output = Result<TAck, MySocketIoError>> + Send + 'static;
+}
+
+impl<TPayload, TAck, TFn, TFuture> MySocketIoTypedHandlerFn<TPayload, TAck> for TFn
+where
+    TFn: Fn(TPayload) -> TFuture + Send + Sync + 'static,
+    TFuture: Future<Output = Result<TAck, MySocketIoError>> + Send + 'static,
+{
+    type Future = TFuture;
+}
+
+pub struct MySocketIoTypedEventHandler<TPayload, TAck, TFn> {
+    handler: TFn,
+    itm: PhantomData<fn(TPayload) -> TAck>,
+}
+
+impl<TPayload, TAck, TFn> MySocketIoTypedEventHandler<TPayload, TAck, TFn>
+where
+    TPayload: DeserializeOwned + Send + 'static,
+    TAck: Serialize + Send + 'static,
+    TFn: MySocketIoTypedHandlerFn<TPayload, TAck>,
+{
+    pub fn new(handler: TFn) -> Self {
+        Self {
+            handler,
+            itm: PhantomData,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<TPayload, TAck, TFn> MySocketIoEventHandler
+    for MySocketIoTypedEventHandler<TPayload, TAck, TFn>
+where
+    TPayload: DeserializeOwned + Send + 'static,
+    TAck: Serialize + Send + 'static,
+    TFn: MySocketIoTypedHandlerFn<TPayload, TAck>,
+{
+    async fn handle(
+        &self,
+        event: &MySocketIoEvent,
+        ack: Option<MySocketIoAckResponder>,
+    ) -> Result<(), MySocketIoError> {
+        let payload: TPayload = match serde_json::from_str(event.event_data.as_str()) {
+            Ok(payload) => payload,
+            Err(err) => {
+                return Err(MySocketIoError::with_data(
+                    INVALID_PAYLOAD_ERROR,
+                    serde_json::json!({
+                        "event": event.event_name,
+                        "reason": err.to_string(),
+                    })
+                    .to_string(),
+                ));
+            }
+        };
+
+        let result = (self.handler)(payload).await?;
+
+        let ack = match ack {
+            Some(ack) => ack,
+            None => return Ok(()),
+        };
+
+        match serde_json::to_string(&[result]) {
+            Ok(ack_data) => {
+                ack.send(ack_data).await;
+                Ok(())
+            }
+            Err(err) => Err(MySocketIoError::new(format!(
+                "Can not serialize ack for event {}: {}",
+                event.event_name, err
+            ))),
+        }
+    }
+}
+
+pub struct MySocketIoRawEventHandler<TFn> {
+    handler: TFn,
+}
+
+impl<TFn, TFuture> MySocketIoRawEventHandler<TFn>
+where
+    TFn: Fn(MySocketIoEvent, Option<MySocketIoAckResponder>) -> TFuture + Send + Sync + 'static,
+    TFuture: Future<Output = Result<(), MySocketIoError>> + Send + 'static,
+{
+    pub fn new(handler: TFn) -> Self {
+        Self { handler }
+    }
+}
+
+#[async_trait::async_trait]
+impl<TFn, TFuture> MySocketIoEventHandler for MySocketIoRawEventHandler<TFn>
+where
+    TFn: Fn(MySocketIoEvent, Option<MySocketIoAckResponder>) -> TFuture + Send + Sync + 'static,
+    TFuture: Future<Output = Result<(), MySocketIoError>> + Send + 'static,
+{
+    async fn handle(
+        &self,
+        event: &MySocketIoEvent,
+        ack: Option<MySocketIoAckResponder>,
+    ) -> Result<(), MySocketIoError> {
+        (self.handler)(event.clone(), ack).await
+    }
+}
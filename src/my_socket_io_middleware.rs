@@ -0,0 +1,22 @@
+use std::sync::Arc;
+
+use crate::{MySocketIoConnection, MySocketIoError, MySocketIoEvent};
+
+#[async_trait::async_trait]
+pub trait MySocketIoMiddleware {
+    async fn on_connect(
+        &self,
+        _socket_io: &Arc<MySocketIoConnection>,
+        _nsp: &str,
+    ) -> Result<(), MySocketIoError> {
+        Ok(())
+    }
+
+    async fn on_event(
+        &self,
+        _socket_io: &Arc<MySocketIoConnection>,
+        _event: &mut MySocketIoEvent,
+    ) -> Result<(), MySocketIoError> {
+        Ok(())
+    }
+}
@@ -0,0 +1,474 @@
+use std::{collections::HashMap, future::Future, sync::Arc};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    MySocketIo, MySocketIoAckResponder, MySocketIoError, MySocketIoEvent, MySocketIoEventHandler,
+    MySocketIoRawEventHandler, MySocketIoTypedEventHandler, MySocketIoTypedHandlerFn,
+};
+
+pub const UNKNOWN_EVENT_ERROR: &str = "Unknown event";
+pub const ACK_REQUIRED_ERROR: &str = "Ack required";
+pub const PAYLOAD_TOO_LARGE_ERROR: &str = "Payload too large";
+
+#[derive(Debug, Clone, Default)]
+pub struct MySocketIoEventOptions {
+    pub ack_required: bool,
+    pub max_payload_size: Option<usize>,
+}
+
+struct NamespaceRoute {
+    handler: Arc<dyn MySocketIoEventHandler + Send + Sync + 'static>,
+    options: MySocketIoEventOptions,
+}
+
+impl NamespaceRoute {
+    fn check_options(
+        &self,
+        event: &MySocketIoEvent,
+        default_max_payload_size: Option<usize>,
+    ) -> Result<(), MySocketIoError> {
+        if self.options.ack_required && !event.ack_requested {
+            return Err(MySocketIoError::with_data(
+                ACK_REQUIRED_ERROR,
+                serde_json::json!({ "event": event.event_name }).to_string(),
+            ));
+        }
+
+        if let Some(max_payload_size) = self.options.max_payload_size.or(default_max_payload_size) {
+            let size = event.get_payload_size();
+            if size > max_payload_size {
+                return Err(MySocketIoError::with_data(
+                    PAYLOAD_TOO_LARGE_ERROR,
+                    serde_json::json!({
+                        "event": event.event_name,
+                        "size": size,
+                        "maxSize": max_payload_size,
+                    })
+                    .to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Routes events of one namespace to handlers.
+// Lookup order: exact name, then prefix routes ("orders.*") in registration order, then fallback.
+// The catch-all handler sees every event before routing and never gets the ack responder.
+pub struct NamespaceRouter {
+    nsp: String,
+    routes: HashMap<String, NamespaceRoute>,
+    prefix_routes: Vec<(String, NamespaceRoute)>,
+    catch_all: Option<Arc<dyn MySocketIoEventHandler + Send + Sync + 'static>>,
+    fallback: Option<Arc<dyn MySocketIoEventHandler + Send + Sync + 'static>>,
+    max_payload_size: Option<usize>,
+}
+
+impl NamespaceRouter {
+    pub fn new(nsp: &str) -> Self {
+        Self {
+            nsp: nsp.to_string(),
+            routes: HashMap::new(),
+            prefix_routes: Vec::new(),
+            catch_all: None,
+            fallback: None,
+            max_payload_size: None,
+        }
+    }
+
+    pub fn on<TPayload, TAck>(
+        &mut self,
+        event_name: &str,
+        handler: impl MySocketIoTypedHandlerFn<TPayload, TAck>,
+    ) -> &mut Self
+    where
+        TPayload: DeserializeOwned + Send + 'static,
+        TAck: Serialize + Send + 'static,
+    {
+        self.on_with_options(event_name, MySocketIoEventOptions::default(), handler)
+    }
+
+    pub fn on_with_options<TPayload, TAck>(
+        &mut self,
+        event_name: &str,
+        options: MySocketIoEventOptions,
+        handler: impl MySocketIoTypedHandlerFn<TPayload, TAck>,
+    ) -> &mut Self
+    where
+        TPayload: DeserializeOwned + Send + 'static,
+        TAck: Serialize + Send + 'static,
+    {
+        self.add_route(
+            event_name,
+            NamespaceRoute {
+                handler: Arc::new(MySocketIoTypedEventHandler::new(handler)),
+                options,
+            },
+        );
+        self
+    }
+
+    pub fn on_raw<TFn, TFuture>(&mut self, event_name: &str, handler: TFn) -> &mut Self
+    where
+        TFn: Fn(MySocketIoEvent, Option<MySocketIoAckResponder>) -> TFuture + Send + Sync + 'static,
+        TFuture: Future<Output = Result<(), MySocketIoError>> + Send + 'static,
+    {
+        self.on_handler(
+            event_name,
+            MySocketIoEventOptions::default(),
+            Arc::new(MySocketIoRawEventHandler::new(handler)),
+        )
+    }
+
+    pub fn on_handler(
+        &mut self,
+        event_name: &str,
+        options: MySocketIoEventOptions,
+        handler: Arc<dyn MySocketIoEventHandler + Send + Sync + 'static>,
+    ) -> &mut Self {
+        self.add_route(event_name, NamespaceRoute { handler, options });
+        self
+    }
+
+    pub fn on_any<TFn, TFuture>(&mut self, handler: TFn) -> &mut Self
+    where
+        TFn: Fn(MySocketIoEvent, Option<MySocketIoAckResponder>) -> TFuture + Send + Sync + 'static,
+        TFuture: Future<Output = Result<(), MySocketIoError>> + Send + 'static,
+    {
+        self.catch_all = Some(Arc::new(MySocketIoRawEventHandler::new(handler)));
+        self
+    }
+
+    pub fn set_fallback<TFn, TFuture>(&mut self, handler: TFn) -> &mut Self
+    where
+        TFn: Fn(MySocketIoEvent, Option<MySocketIoAckResponder>) -> TFuture + Send + Sync + 'static,
+        TFuture: Future<Output = Result<(), MySocketIoError>> + Send + 'static,
+    {
+        self.fallback = Some(Arc::new(MySocketIoRawEventHandler::new(handler)));
+        self
+    }
+
+    // Applies to every route which does not have max_payload_size in its own options
+    pub fn set_max_payload_size(&mut self, max_payload_size: usize) -> &mut Self {
+        self.max_payload_size = Some(max_payload_size);
+        self
+    }
+
+    fn add_route(&mut self, event_name: &str, route: NamespaceRoute) {
+        if let Some(prefix) = event_name.strip_suffix('*') {
+            self.prefix_routes.push((prefix.to_string(), route));
+        } else {
+            self.routes.insert(event_name.to_string(), route);
+        }
+    }
+
+    fn find_route(&self, event_name: &str) -> Option<&NamespaceRoute> {
+        if let Some(route) = self.routes.get(event_name) {
+            return Some(route);
+        }
+
+        for (prefix, route) in &self.prefix_routes {
+            if event_name.starts_with(prefix.as_str()) {
+                return Some(route);
+            }
+        }
+
+        None
+    }
+}
+
+#[async_trait::async_trait]
+impl MySocketIo for NamespaceRouter {
+    async fn on(
+        &self,
+        event: &MySocketIoEvent,
+        ack: Option<MySocketIoAckResponder>,
+    ) -> Result<(), MySocketIoError> {
+        if let Some(catch_all) = &self.catch_all {
+            catch_all.handle(event, None).await?;
+        }
+
+        if let Some(route) = self.find_route(event.event_name.as_str()) {
+            route.check_options(event, self.max_payload_size)?;
+            return route.handler.handle(event, ack).await;
+        }
+
+        match &self.fallback {
+            Some(fallback) => fallback.handle(event, ack).await,
+            None => Err(MySocketIoError::with_data(
+                UNKNOWN_EVENT_ERROR,
+                serde_json::json!({ "event": event.event_name }).to_string(),
+            )),
+        }
+    }
+
+    fn get_nsp(&self) -> &str {
+        self.nsp.as_str()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use my_http_server::HttpFailResult;
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::{
+        MySocketIoConnection, MySocketIoConnectionsCallbacks, MySocketIoEngineMiddleware,
+        MySocketIoJsonParser, MySocketIoMetrics, MySocketIoSendQueueOptions, INVALID_PAYLOAD_ERROR,
+    };
+
+    #[derive(Deserialize)]
+    struct OrderPayload {
+        id: i64,
+    }
+
+    type CallsLog = Arc<Mutex<Vec<&'static str>>>;
+
+    struct RecordHandler {
+        name: &'static str,
+        calls: CallsLog,
+    }
+
+    #[async_trait::async_trait]
+    impl MySocketIoEventHandler for RecordHandler {
+        async fn handle(
+            &self,
+            _event: &MySocketIoEvent,
+            _ack: Option<MySocketIoAckResponder>,
+        ) -> Result<(), MySocketIoError> {
+            self.calls.lock().unwrap().push(self.name);
+            Ok(())
+        }
+    }
+
+    fn record(name: &'static str, calls: &CallsLog) -> Arc<RecordHandler> {
+        Arc::new(RecordHandler {
+            name,
+            calls: calls.clone(),
+        })
+    }
+
+    fn create_event(event_name: &str, event_data: &str, ack_requested: bool) -> MySocketIoEvent {
+        MySocketIoEvent {
+            nsp: "/".to_string(),
+            event_name: event_name.to_string(),
+            event_data: event_data.to_string(),
+            args: vec![event_data.to_string()],
+            ack_requested,
+        }
+    }
+
+    async fn dispatch(
+        router: &NamespaceRouter,
+        event: MySocketIoEvent,
+    ) -> Result<(), MySocketIoError> {
+        MySocketIo::on(router, &event, None).await
+    }
+
+    #[tokio::test]
+    async fn test_exact_route_goes_before_prefix_route() {
+        let calls = CallsLog::default();
+        let mut router = NamespaceRouter::new("/");
+        router.on_handler("orders.*", Default::default(), record("prefix", &calls));
+        router.on_handler("orders.create", Default::default(), record("exact", &calls));
+
+        dispatch(&router, create_event("orders.create", "1", false))
+            .await
+            .unwrap();
+        dispatch(&router, create_event("orders.cancel", "1", false))
+            .await
+            .unwrap();
+
+        assert_eq!(vec!["exact", "prefix"], *calls.lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_prefix_routes_are_matched_in_registration_order() {
+        let calls = CallsLog::default();
+        let mut router = NamespaceRouter::new("/");
+        router.on_handler("orders.*", Default::default(), record("first", &calls));
+        router.on_handler(
+            "orders.create*",
+            Default::default(),
+            record("second", &calls),
+        );
+
+        dispatch(&router, create_event("orders.create", "1", false))
+            .await
+            .unwrap();
+
+        assert_eq!(vec!["first"], *calls.lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_ack_required_route_rejects_event_without_ack() {
+        let calls = CallsLog::default();
+        let mut router = NamespaceRouter::new("/");
+        let options = MySocketIoEventOptions {
+            ack_required: true,
+            max_payload_size: None,
+        };
+        router.on_handler("order", options, record("order", &calls));
+
+        let err = dispatch(&router, create_event("order", "1", false))
+            .await
+            .unwrap_err();
+        assert_eq!(ACK_REQUIRED_ERROR, err.message);
+        assert!(calls.lock().unwrap().is_empty());
+
+        dispatch(&router, create_event("order", "1", true))
+            .await
+            .unwrap();
+        assert_eq!(vec!["order"], *calls.lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_route_max_payload_size_overrides_router_one() {
+        let calls = CallsLog::default();
+        let mut router = NamespaceRouter::new("/");
+        router.set_max_payload_size(5);
+        router.on_handler("small", Default::default(), record("small", &calls));
+        let options = MySocketIoEventOptions {
+            ack_required: false,
+            max_payload_size: Some(100),
+        };
+        router.on_handler("big", options, record("big", &calls));
+
+        let err = dispatch(&router, create_event("small", "\"123456\"", false))
+            .await
+            .unwrap_err();
+        assert_eq!(PAYLOAD_TOO_LARGE_ERROR, err.message);
+
+        dispatch(&router, create_event("big", "\"123456\"", false))
+            .await
+            .unwrap();
+
+        assert_eq!(vec!["big"], *calls.lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_event_without_fallback_is_error() {
+        let router = NamespaceRouter::new("/");
+
+        let err = dispatch(&router, create_event("unknown", "1", false))
+            .await
+            .unwrap_err();
+        assert_eq!(UNKNOWN_EVENT_ERROR, err.message);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_event_goes_to_fallback() {
+        let calls = CallsLog::default();
+        let mut router = NamespaceRouter::new("/");
+        let fallback_calls = calls.clone();
+        router.set_fallback(move |_event, _ack| {
+            let calls = fallback_calls.clone();
+            async move {
+                calls.lock().unwrap().push("fallback");
+                Ok(())
+            }
+        });
+
+        dispatch(&router, create_event("unknown", "1", false))
+            .await
+            .unwrap();
+
+        assert_eq!(vec!["fallback"], *calls.lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_catch_all_error_stops_dispatch() {
+        let calls = CallsLog::default();
+        let mut router = NamespaceRouter::new("/");
+        router.on_any(|_event, _ack| async move { Err(MySocketIoError::new("Forbidden")) });
+        router.on_handler("order", Default::default(), record("order", &calls));
+
+        let err = dispatch(&router, create_event("order", "1", false))
+            .await
+            .unwrap_err();
+        assert_eq!("Forbidden", err.message);
+        assert!(calls.lock().unwrap().is_empty());
+    }
+
+    struct TestConnectionsCallbacks;
+
+    #[async_trait::async_trait]
+    impl MySocketIoConnectionsCallbacks for TestConnectionsCallbacks {
+        async fn connected(
+            &self,
+            _socket_io: Arc<MySocketIoConnection>,
+        ) -> Result<(), HttpFailResult> {
+            Ok(())
+        }
+
+        async fn disconnected(&self, _socket_io: Arc<MySocketIoConnection>) {}
+    }
+
+    async fn create_engine(
+        router: NamespaceRouter,
+    ) -> (MySocketIoEngineMiddleware, Arc<MySocketIoConnection>) {
+        let engine = MySocketIoEngineMiddleware::new(Arc::new(TestConnectionsCallbacks));
+        engine.register_socket_io(Arc::new(router)).await;
+
+        let socket_io = Arc::new(MySocketIoConnection::new(
+            "sid1".to_string(),
+            None,
+            None,
+            MySocketIoSendQueueOptions::default(),
+            Arc::new(MySocketIoJsonParser),
+            Arc::new(MySocketIoMetrics::new()),
+        ));
+        socket_io.add_namespace("/").await;
+        engine.get_socket_io_list().add_socket_io(socket_io.clone());
+
+        (engine, socket_io)
+    }
+
+    // Everything queued for the client, the way the next poll gets it
+    async fn get_sent(socket_io: &MySocketIoConnection) -> String {
+        let awaiter = socket_io.set_long_pooling_task().await;
+        socket_io.flush_send_queue().await;
+        awaiter.get_result().await.unwrap().payload
+    }
+
+    #[tokio::test]
+    async fn test_typed_handler_with_payload_and_ack_types() {
+        let mut router = NamespaceRouter::new("/");
+        router.on::<OrderPayload, i64>("order", |payload| async move { Ok(payload.id) });
+
+        let (engine, socket_io) = create_engine(router).await;
+
+        assert!(
+            engine
+                .receive_packets("sid1", "421[\"order\",{\"id\":42}]")
+                .await
+        );
+
+        let sent = get_sent(&socket_io).await;
+        assert!(sent.starts_with("431"), "{}", sent);
+        assert!(sent.ends_with("[42]"), "{}", sent);
+    }
+
+    #[tokio::test]
+    async fn test_typed_handler_with_mismatched_payload_acks_error() {
+        let mut router = NamespaceRouter::new("/");
+        router.on::<OrderPayload, i64>("order", |payload| async move { Ok(payload.id) });
+
+        let (engine, socket_io) = create_engine(router).await;
+
+        assert!(
+            engine
+                .receive_packets("sid1", "421[\"order\",\"oops\"]")
+                .await
+        );
+
+        let sent = get_sent(&socket_io).await;
+        assert!(sent.starts_with("431"), "{}", sent);
+        assert!(sent.contains(INVALID_PAYLOAD_ERROR), "{}", sent);
+    }
+}
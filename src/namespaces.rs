@@ -2,31 +2,195 @@ use std::{collections::HashMap, sync::Arc};
 
 use tokio::sync::Mutex;
 
-use crate::MySocketIo;
+use crate::{
+    sharded_map::ShardedMap, MySocketIo, MySocketIoDynamicNamespace, MySocketIoMiddleware,
+};
 
+// Connect error of a namespace which is not registered and not matched by a dynamic one
+pub const INVALID_NAMESPACE_ERROR: &str = "Invalid namespace";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NamespaceKind {
+    Static,
+    Dynamic,
+}
+
+// Handler of the namespace with its middlewares. Middlewares of the dynamic parent go first
+#[derive(Clone)]
+pub struct NamespaceEntry {
+    pub socket: Arc<dyn MySocketIo + Send + Sync + 'static>,
+    pub middlewares: Arc<Vec<Arc<dyn MySocketIoMiddleware + Send + Sync + 'static>>>,
+}
+
+struct DynamicChild {
+    parent: Arc<MySocketIoDynamicNamespace>,
+    connections: usize,
+}
+
+struct SocketIoNameSpacesInner {
+    sockets: HashMap<String, Arc<dyn MySocketIo + Send + Sync + 'static>>,
+    middlewares: HashMap<String, Vec<Arc<dyn MySocketIoMiddleware + Send + Sync + 'static>>>,
+    dynamic: Vec<Arc<MySocketIoDynamicNamespace>>,
+    dynamic_children: HashMap<String, DynamicChild>,
+}
+
+impl SocketIoNameSpacesInner {
+    fn get_entry(&self, nsp: &str) -> Option<NamespaceEntry> {
+        let socket = self.sockets.get(nsp)?.clone();
+
+        let mut middlewares = match self.dynamic_children.get(nsp) {
+            Some(child) => child.parent.get_middlewares().to_vec(),
+            None => Vec::new(),
+        };
+
+        if let Some(items) = self.middlewares.get(nsp) {
+            middlewares.extend(items.iter().cloned());
+        }
+
+        Some(NamespaceEntry {
+            socket,
+            middlewares: Arc::new(middlewares),
+        })
+    }
+}
+
+// Registrations, dynamic namespaces and their connection counts change under the mutex.
+// Every change publishes the entry of the namespace to the sharded map,
+// so event dispatch reads it without taking the mutex
 pub struct SocketIoNameSpaces {
-    items: Mutex<HashMap<String, Arc<dyn MySocketIo + Send + Sync + 'static>>>,
+    items: Mutex<SocketIoNameSpacesInner>,
+    entries: ShardedMap<String, NamespaceEntry>,
 }
 
 impl SocketIoNameSpaces {
     pub fn new() -> Self {
         Self {
-            items: Mutex::new(HashMap::new()),
+            items: Mutex::new(SocketIoNameSpacesInner {
+                sockets: HashMap::new(),
+                middlewares: HashMap::new(),
+                dynamic: Vec::new(),
+                dynamic_children: HashMap::new(),
+            }),
+            entries: ShardedMap::new(),
         }
     }
 
-    pub async fn get(&self, nsp: &str) -> Option<Arc<dyn MySocketIo + Send + Sync + 'static>> {
-        let read_access = self.items.lock().await;
-        read_access.get(nsp).cloned()
+    fn publish(&self, inner: &SocketIoNameSpacesInner, nsp: &str) {
+        match inner.get_entry(nsp) {
+            Some(entry) => {
+                self.entries.insert(nsp.to_string(), entry);
+            }
+            None => {
+                self.entries.remove(nsp);
+            }
+        }
+    }
+
+    pub fn get(&self, nsp: &str) -> Option<NamespaceEntry> {
+        self.entries.get(nsp)
     }
 
     pub async fn add(&self, socket: Arc<dyn MySocketIo + Send + Sync + 'static>) {
         let mut write_access = self.items.lock().await;
-        write_access.insert(socket.get_nsp().to_string(), socket);
+        let nsp = socket.get_nsp().to_string();
+        write_access.sockets.insert(nsp.clone(), socket);
+        self.publish(&write_access, nsp.as_str());
+    }
+
+    pub async fn add_dynamic(&self, dynamic: MySocketIoDynamicNamespace) {
+        let mut write_access = self.items.lock().await;
+        write_access.dynamic.push(Arc::new(dynamic));
+    }
+
+    // Finds namespace by exact name or creates it by the first matching dynamic namespace.
+    // Acquiring a dynamic namespace counts one more connection which has to be given back with release_dynamic
+    pub async fn acquire(&self, nsp: &str) -> Option<NamespaceKind> {
+        let parent = {
+            let mut write_access = self.items.lock().await;
+
+            if let Some(kind) = acquire_existing(&mut write_access, nsp) {
+                return Some(kind);
+            }
+
+            write_access
+                .dynamic
+                .iter()
+                .find(|itm| itm.is_match(nsp))
+                .cloned()?
+        };
+
+        // Factory can be slow (tenant settings from a database), so it runs without the lock
+        let socket = parent.create(nsp)?;
+
+        let mut write_access = self.items.lock().await;
+
+        // Concurrent connect could have created the namespace while the factory was running
+        if let Some(kind) = acquire_existing(&mut write_access, nsp) {
+            return Some(kind);
+        }
+
+        tracing::info!(nsp, "Socket.IO dynamic namespace is created");
+
+        write_access.sockets.insert(nsp.to_string(), socket);
+        write_access.dynamic_children.insert(
+            nsp.to_string(),
+            DynamicChild {
+                parent,
+                connections: 1,
+            },
+        );
+        self.publish(&write_access, nsp);
+
+        Some(NamespaceKind::Dynamic)
+    }
+
+    pub async fn release_dynamic(&self, nsp: &str) {
+        let mut write_access = self.items.lock().await;
+
+        let child = match write_access.dynamic_children.get_mut(nsp) {
+            Some(child) => child,
+            None => return,
+        };
+
+        child.connections = child.connections.saturating_sub(1);
+
+        if child.connections > 0 || !child.parent.is_cleanup_when_empty() {
+            return;
+        }
+
+        write_access.dynamic_children.remove(nsp);
+        write_access.sockets.remove(nsp);
+        self.publish(&write_access, nsp);
+
+        tracing::info!(nsp, "Socket.IO dynamic namespace is empty and removed");
+    }
+
+    pub async fn add_middleware(
+        &self,
+        nsp: &str,
+        middleware: Arc<dyn MySocketIoMiddleware + Send + Sync + 'static>,
+    ) {
+        let mut write_access = self.items.lock().await;
+
+        write_access
+            .middlewares
+            .entry(nsp.to_string())
+            .or_default()
+            .push(middleware);
+
+        self.publish(&write_access, nsp);
+    }
+}
+
+fn acquire_existing(inner: &mut SocketIoNameSpacesInner, nsp: &str) -> Option<NamespaceKind> {
+    if let Some(child) = inner.dynamic_children.get_mut(nsp) {
+        child.connections += 1;
+        return Some(NamespaceKind::Dynamic);
     }
 
-    pub async fn has_nsp(&self, nsp: &str) -> bool {
-        let read_access = self.items.lock().await;
-        return read_access.contains_key(nsp);
+    if inner.sockets.contains_key(nsp) {
+        return Some(NamespaceKind::Static);
     }
+
+    None
 }
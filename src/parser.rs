@@ -0,0 +1,37 @@
+use hyper_tungstenite::tungstenite::Message;
+use my_http_server_web_sockets::WebSocketMessage;
+
+use crate::MySocketIoProtocolError;
+
+// Engine.IO MESSAGE packet type. Every Socket.IO packet is carried inside of it
+pub const ENGINE_IO_MESSAGE_PREFIX: char = '4';
+
+// Converts Engine.IO packets between the text encoding the engine works with and WebSocket frames.
+// Engine.IO control packets (ping, pong, upgrade, close) stay text frames for every parser
+pub trait MySocketIoParser {
+    fn get_name(&self) -> &'static str;
+
+    fn encode(&self, payload: String) -> Message;
+
+    // Ok(None) - frame is not supported by the parser and is ignored
+    fn decode(&self, message: WebSocketMessage) -> Result<Option<String>, MySocketIoProtocolError>;
+}
+
+pub struct MySocketIoJsonParser;
+
+impl MySocketIoParser for MySocketIoJsonParser {
+    fn get_name(&self) -> &'static str {
+        "json"
+    }
+
+    fn encode(&self, payload: String) -> Message {
+        Message::Text(payload)
+    }
+
+    fn decode(&self, message: WebSocketMessage) -> Result<Option<String>, MySocketIoProtocolError> {
+        match message {
+            WebSocketMessage::String(value) => Ok(Some(value)),
+            WebSocketMessage::Binary(_) => Ok(None),
+        }
+    }
+}
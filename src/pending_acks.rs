@@ -0,0 +1,74 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Mutex,
+    },
+};
+
+use tokio::sync::oneshot;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MySocketIoAckError {
+    Timeout,
+    Disconnected,
+}
+
+// Acks of server to client requests. Packet ids are unique within the connection
+pub struct PendingAcks {
+    next_id: AtomicI64,
+    items: Mutex<HashMap<i64, oneshot::Sender<Result<String, MySocketIoAckError>>>>,
+}
+
+impl PendingAcks {
+    pub fn new() -> Self {
+        Self {
+            next_id: AtomicI64::new(0),
+            items: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn register(&self) -> (i64, oneshot::Receiver<Result<String, MySocketIoAckError>>) {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = oneshot::channel();
+
+        let mut write_access = self.items.lock().unwrap();
+        write_access.insert(id, sender);
+
+        (id, receiver)
+    }
+
+    // Returns false if nobody waits for the ack (timed out or unknown id)
+    pub fn resolve(&self, id: i64, data: String) -> bool {
+        let sender = {
+            let mut write_access = self.items.lock().unwrap();
+            write_access.remove(&id)
+        };
+
+        match sender {
+            Some(sender) => sender.send(Ok(data)).is_ok(),
+            None => false,
+        }
+    }
+
+    pub fn remove(&self, id: i64) {
+        let mut write_access = self.items.lock().unwrap();
+        write_access.remove(&id);
+    }
+
+    pub fn cancel_all(&self) {
+        let items: Vec<_> = {
+            let mut write_access = self.items.lock().unwrap();
+            write_access.drain().collect()
+        };
+
+        for (_, sender) in items {
+            let _ = sender.send(Err(MySocketIoAckError::Disconnected));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        let read_access = self.items.lock().unwrap();
+        read_access.len()
+    }
+}
@@ -0,0 +1,34 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+
+// Counts a dispatch in progress. Shared with the ack responder, so the dispatch is pending until the ack is sent.
+// Decremented on drop as well, so a panicking handler, a cancelled dispatch future or a never answered ack
+// does not leave shutdown waiting for the whole grace period
+pub(crate) struct PendingDispatchGuard {
+    pending_dispatches: Arc<AtomicUsize>,
+    released: AtomicBool,
+}
+
+impl PendingDispatchGuard {
+    pub fn new(pending_dispatches: Arc<AtomicUsize>) -> Self {
+        pending_dispatches.fetch_add(1, Ordering::SeqCst);
+        Self {
+            pending_dispatches,
+            released: AtomicBool::new(false),
+        }
+    }
+
+    pub fn release(&self) {
+        if !self.released.swap(true, Ordering::SeqCst) {
+            self.pending_dispatches.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Drop for PendingDispatchGuard {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
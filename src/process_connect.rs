@@ -1,16 +1,26 @@
-use std::sync::Arc;
+use std::{net::SocketAddr, sync::Arc};
 
+use my_http_server::HttpFailResult;
 use my_http_server_web_sockets::MyWebSocket;
 use socket_io_utils::SocketIoSettings;
 
-use crate::{MySocketIoConnection, MySocketIoConnectionsCallbacks, SocketIoList};
+use crate::{
+    MySocketIoConnection, MySocketIoConnectionsCallbacks, MySocketIoEngineOptions,
+    MySocketIoMetrics, SocketIoList,
+};
 
+// Caller writes the open packet first and then starts socket_io_writer_loop,
+// so nothing queued by the connected callback gets to the wire before it.
+// Ip slot reserved by the caller is released if the connected callback rejects the connection
 pub async fn process_connect(
     connections_callback: &Arc<dyn MySocketIoConnectionsCallbacks + Send + Sync + 'static>,
     socket_io_list: &Arc<SocketIoList>,
     settings: &Arc<SocketIoSettings>,
+    options: &Arc<MySocketIoEngineOptions>,
+    metrics: &Arc<MySocketIoMetrics>,
     web_socket: Option<Arc<MyWebSocket>>,
-) -> (Arc<MySocketIoConnection>, String) {
+    remote_addr: Option<SocketAddr>,
+) -> Result<(Arc<MySocketIoConnection>, String), HttpFailResult> {
     let sid = uuid::Uuid::new_v4().to_string();
 
     let sid = sid.replace("-", "")[..8].to_string();
@@ -18,17 +28,39 @@ pub async fn process_connect(
     let result =
         socket_io_utils::my_socket_io_messages::compile_negotiate_response(sid.as_str(), settings);
 
-    let socket_io = MySocketIoConnection::new(sid, web_socket);
+    metrics.handshake();
+
+    let socket_io = MySocketIoConnection::new(
+        sid,
+        web_socket,
+        remote_addr,
+        options.send_queue,
+        options.parser.clone(),
+        metrics.clone(),
+    );
     let socket_io_connection = Arc::new(socket_io);
 
-    connections_callback
+    tracing::info!(parent: &socket_io_connection.span, "Socket.IO handshake");
+
+    if let Err(err) = connections_callback
         .connected(socket_io_connection.clone())
         .await
-        .unwrap();
+    {
+        tracing::info!(
+            parent: &socket_io_connection.span,
+            status_code = err.status_code,
+            "Socket.IO connection is rejected"
+        );
+
+        if let Some(remote_addr) = remote_addr {
+            socket_io_list.release_ip_connection(remote_addr.ip());
+        }
+
+        metrics.connection_rejected();
+        return Err(err);
+    }
 
-    socket_io_list
-        .add_socket_io(socket_io_connection.clone())
-        .await;
+    socket_io_list.add_socket_io(socket_io_connection.clone());
 
-    (socket_io_connection, result)
+    Ok((socket_io_connection, result))
 }
@@ -1,16 +1,32 @@
 use std::sync::Arc;
 
-use crate::{MySocketIoConnection, MySocketIoConnectionsCallbacks, SocketIoList};
+use crate::{
+    namespaces::SocketIoNameSpaces, MySocketIoConnection, MySocketIoConnectionsCallbacks,
+    MySocketIoDisconnectReason, SocketIoList,
+};
 
 pub async fn process_disconnect(
     sockets_list: &Arc<SocketIoList>,
+    registered_sockets: &Arc<SocketIoNameSpaces>,
     socket_io_connection: &Arc<MySocketIoConnection>,
     connect_events: &Arc<dyn MySocketIoConnectionsCallbacks + Send + Sync + 'static>,
+    reason: MySocketIoDisconnectReason,
 ) {
     let removed_connection = sockets_list.remove(socket_io_connection.id.as_str()).await;
 
     if let Some(removed_connection) = removed_connection {
-        println!("Socket.IO {} is diconnectd", removed_connection.id);
+        tracing::info!(
+            parent: &removed_connection.span,
+            reason = reason.as_str(),
+            "Socket.IO connection is disconnected"
+        );
+        removed_connection.get_metrics().disconnected(reason);
+
+        // Dynamic namespace is cleaned up after its last connection is gone
+        for nsp in removed_connection.get_namespaces().await {
+            registered_sockets.release_dynamic(nsp.as_str()).await;
+        }
+
         connect_events.disconnected(removed_connection).await;
     }
 }
@@ -0,0 +1,171 @@
+use std::{collections::HashMap, time::Instant};
+
+pub const RATE_LIMIT_EXCEEDED_ERROR: &str = "Rate limit exceeded";
+
+#[derive(Debug, Clone, Copy)]
+pub struct MySocketIoTokenBucketSettings {
+    pub capacity: u32,
+    pub refill_per_second: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MySocketIoRateLimitPolicy {
+    Drop,
+    ErrorAck,
+    Disconnect,
+}
+
+#[derive(Debug, Clone)]
+pub struct MySocketIoRateLimitOptions {
+    pub per_connection: Option<MySocketIoTokenBucketSettings>,
+    pub per_event: HashMap<String, MySocketIoTokenBucketSettings>,
+    pub max_connections_per_ip: Option<usize>,
+    pub policy: MySocketIoRateLimitPolicy,
+}
+
+impl Default for MySocketIoRateLimitOptions {
+    fn default() -> Self {
+        Self {
+            per_connection: None,
+            per_event: HashMap::new(),
+            max_connections_per_ip: None,
+            policy: MySocketIoRateLimitPolicy::ErrorAck,
+        }
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(settings: &MySocketIoTokenBucketSettings) -> Self {
+        Self {
+            tokens: settings.capacity as f64,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn try_consume(&mut self, settings: &MySocketIoTokenBucketSettings) -> bool {
+        self.try_consume_at(settings, Instant::now())
+    }
+
+    fn try_consume_at(&mut self, settings: &MySocketIoTokenBucketSettings, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.last_refill = now;
+
+        self.tokens =
+            (self.tokens + elapsed * settings.refill_per_second).min(settings.capacity as f64);
+
+        if self.tokens < 1.0 {
+            return false;
+        }
+
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+// Buckets are created on first use so connection does not need to know the settings up front
+#[derive(Default)]
+pub(crate) struct MySocketIoRateLimitState {
+    connection: Option<TokenBucket>,
+    events: HashMap<String, TokenBucket>,
+}
+
+impl MySocketIoRateLimitState {
+    pub fn try_consume_connection(&mut self, settings: &MySocketIoTokenBucketSettings) -> bool {
+        if self.connection.is_none() {
+            self.connection = Some(TokenBucket::new(settings));
+        }
+
+        self.connection.as_mut().unwrap().try_consume(settings)
+    }
+
+    pub fn try_consume_event(
+        &mut self,
+        event_name: &str,
+        settings: &MySocketIoTokenBucketSettings,
+    ) -> bool {
+        if !self.events.contains_key(event_name) {
+            self.events
+                .insert(event_name.to_string(), TokenBucket::new(settings));
+        }
+
+        self.events
+            .get_mut(event_name)
+            .unwrap()
+            .try_consume(settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn settings(capacity: u32, refill_per_second: f64) -> MySocketIoTokenBucketSettings {
+        MySocketIoTokenBucketSettings {
+            capacity,
+            refill_per_second,
+        }
+    }
+
+    #[test]
+    fn test_burst_up_to_capacity() {
+        let settings = settings(3, 1.0);
+        let mut bucket = TokenBucket::new(&settings);
+        let now = bucket.last_refill;
+
+        assert!(bucket.try_consume_at(&settings, now));
+        assert!(bucket.try_consume_at(&settings, now));
+        assert!(bucket.try_consume_at(&settings, now));
+        assert!(!bucket.try_consume_at(&settings, now));
+    }
+
+    #[test]
+    fn test_refill_over_time() {
+        let settings = settings(1, 2.0);
+        let mut bucket = TokenBucket::new(&settings);
+        let started = bucket.last_refill;
+
+        assert!(bucket.try_consume_at(&settings, started));
+        assert!(!bucket.try_consume_at(&settings, started + Duration::from_millis(250)));
+        assert!(bucket.try_consume_at(&settings, started + Duration::from_millis(750)));
+    }
+
+    #[test]
+    fn test_refill_is_capped_by_capacity() {
+        let settings = settings(2, 10.0);
+        let mut bucket = TokenBucket::new(&settings);
+        let now = bucket.last_refill + Duration::from_secs(60);
+
+        assert!(bucket.try_consume_at(&settings, now));
+        assert!(bucket.try_consume_at(&settings, now));
+        assert!(!bucket.try_consume_at(&settings, now));
+    }
+
+    #[test]
+    fn test_zero_refill_never_recovers() {
+        let settings = settings(1, 0.0);
+        let mut bucket = TokenBucket::new(&settings);
+        let started = bucket.last_refill;
+
+        assert!(bucket.try_consume_at(&settings, started));
+        assert!(!bucket.try_consume_at(&settings, started + Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn test_event_buckets_are_independent() {
+        let settings = settings(1, 0.0);
+        let mut state = MySocketIoRateLimitState::default();
+
+        assert!(state.try_consume_event("a", &settings));
+        assert!(!state.try_consume_event("a", &settings));
+        assert!(state.try_consume_event("b", &settings));
+        assert!(state.try_consume_connection(&settings));
+        assert!(!state.try_consume_connection(&settings));
+    }
+}
@@ -0,0 +1,286 @@
+use std::{collections::VecDeque, sync::Mutex};
+
+use tokio::sync::Notify;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MySocketIoSlowConsumerPolicy {
+    Drop,
+    Coalesce,
+    Disconnect,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MySocketIoSendQueueOptions {
+    pub high_water_mark: usize,
+    pub slow_consumer_policy: MySocketIoSlowConsumerPolicy,
+}
+
+impl Default for MySocketIoSendQueueOptions {
+    fn default() -> Self {
+        Self {
+            high_water_mark: 1000,
+            slow_consumer_policy: MySocketIoSlowConsumerPolicy::Drop,
+        }
+    }
+}
+
+pub struct OutgoingMessage {
+    pub payload: String,
+    pub coalesce_key: Option<String>,
+    pub compress: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnqueueResult {
+    Queued,
+    Coalesced,
+    Dropped,
+    SlowConsumer,
+}
+
+pub struct SendQueue {
+    items: Mutex<VecDeque<OutgoingMessage>>,
+    notify: Notify,
+    options: MySocketIoSendQueueOptions,
+}
+
+impl SendQueue {
+    pub fn new(options: MySocketIoSendQueueOptions) -> Self {
+        Self {
+            items: Mutex::new(VecDeque::new()),
+            notify: Notify::new(),
+            options,
+        }
+    }
+
+    pub fn enqueue(&self, message: OutgoingMessage) -> EnqueueResult {
+        let result = {
+            let mut write_access = self.items.lock().unwrap();
+
+            if write_access.len() < self.options.high_water_mark {
+                write_access.push_back(message);
+                EnqueueResult::Queued
+            } else {
+                match self.options.slow_consumer_policy {
+                    MySocketIoSlowConsumerPolicy::Drop => EnqueueResult::Dropped,
+                    MySocketIoSlowConsumerPolicy::Coalesce => {
+                        match replace_by_key(&mut write_access, message) {
+                            None => EnqueueResult::Coalesced,
+                            Some(_) => EnqueueResult::Dropped,
+                        }
+                    }
+                    MySocketIoSlowConsumerPolicy::Disconnect => EnqueueResult::SlowConsumer,
+                }
+            }
+        };
+
+        self.notify.notify_one();
+        result
+    }
+
+    // For control packets (ping, ack, connect error, disconnect) and emit_with_ack requests.
+    // Queued regardless of the high water mark and never dropped or coalesced
+    pub fn enqueue_control(&self, message: OutgoingMessage) {
+        {
+            let mut write_access = self.items.lock().unwrap();
+            write_access.push_back(message);
+        }
+
+        self.notify.notify_one();
+    }
+
+    // Replaces a queued but not yet sent message with the same key regardless of the high water mark.
+    // If there is nothing to replace - behaves as enqueue
+    pub fn enqueue_latest(&self, message: OutgoingMessage) -> EnqueueResult {
+        let message = {
+            let mut write_access = self.items.lock().unwrap();
+            match replace_by_key(&mut write_access, message) {
+                None => return EnqueueResult::Coalesced,
+                Some(message) => message,
+            }
+        };
+
+        self.enqueue(message)
+    }
+
+    pub fn dequeue(&self) -> Option<OutgoingMessage> {
+        let mut write_access = self.items.lock().unwrap();
+        write_access.pop_front()
+    }
+
+    pub fn get_slow_consumer_policy(&self) -> MySocketIoSlowConsumerPolicy {
+        self.options.slow_consumer_policy
+    }
+
+    pub fn is_over_high_water_mark(&self) -> bool {
+        self.len() >= self.options.high_water_mark
+    }
+
+    pub fn len(&self) -> usize {
+        let read_access = self.items.lock().unwrap();
+        read_access.len()
+    }
+
+    pub fn wake_up(&self) {
+        self.notify.notify_one();
+    }
+
+    pub async fn wait(&self) {
+        self.notify.notified().await;
+    }
+}
+
+// Gives the message back if there is no queued message with the same key
+fn replace_by_key(
+    items: &mut VecDeque<OutgoingMessage>,
+    message: OutgoingMessage,
+) -> Option<OutgoingMessage> {
+    let key = match message.coalesce_key.clone() {
+        Some(key) => key,
+        None => return Some(message),
+    };
+
+    for item in items.iter_mut() {
+        if item.coalesce_key.as_ref() == Some(&key) {
+            *item = message;
+            return None;
+        }
+    }
+
+    Some(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_queue(
+        high_water_mark: usize,
+        slow_consumer_policy: MySocketIoSlowConsumerPolicy,
+    ) -> SendQueue {
+        SendQueue::new(MySocketIoSendQueueOptions {
+            high_water_mark,
+            slow_consumer_policy,
+        })
+    }
+
+    fn message(payload: &str, coalesce_key: Option<&str>) -> OutgoingMessage {
+        OutgoingMessage {
+            payload: payload.to_string(),
+            coalesce_key: coalesce_key.map(|key| key.to_string()),
+            compress: true,
+        }
+    }
+
+    fn drain(queue: &SendQueue) -> Vec<String> {
+        let mut result = Vec::new();
+        while let Some(message) = queue.dequeue() {
+            result.push(message.payload);
+        }
+        result
+    }
+
+    #[test]
+    fn test_messages_are_dequeued_in_order() {
+        let queue = create_queue(10, MySocketIoSlowConsumerPolicy::Drop);
+
+        assert_eq!(EnqueueResult::Queued, queue.enqueue(message("1", None)));
+        assert_eq!(EnqueueResult::Queued, queue.enqueue(message("2", None)));
+
+        assert_eq!(vec!["1", "2"], drain(&queue));
+    }
+
+    #[test]
+    fn test_drop_policy_over_high_water_mark() {
+        let queue = create_queue(2, MySocketIoSlowConsumerPolicy::Drop);
+
+        queue.enqueue(message("1", None));
+        queue.enqueue(message("2", None));
+        assert!(queue.is_over_high_water_mark());
+
+        assert_eq!(EnqueueResult::Dropped, queue.enqueue(message("3", None)));
+        assert_eq!(vec!["1", "2"], drain(&queue));
+    }
+
+    #[test]
+    fn test_coalesce_policy_replaces_message_with_the_same_key() {
+        let queue = create_queue(2, MySocketIoSlowConsumerPolicy::Coalesce);
+
+        queue.enqueue(message("price-1", Some("price")));
+        queue.enqueue(message("order", Some("order")));
+
+        assert_eq!(
+            EnqueueResult::Coalesced,
+            queue.enqueue(message("price-2", Some("price")))
+        );
+        assert_eq!(vec!["price-2", "order"], drain(&queue));
+    }
+
+    #[test]
+    fn test_coalesce_policy_drops_message_without_matching_key() {
+        let queue = create_queue(1, MySocketIoSlowConsumerPolicy::Coalesce);
+
+        queue.enqueue(message("price-1", Some("price")));
+
+        assert_eq!(
+            EnqueueResult::Dropped,
+            queue.enqueue(message("order", Some("order")))
+        );
+        assert_eq!(
+            EnqueueResult::Dropped,
+            queue.enqueue(message("other", None))
+        );
+        assert_eq!(vec!["price-1"], drain(&queue));
+    }
+
+    #[test]
+    fn test_disconnect_policy_reports_slow_consumer() {
+        let queue = create_queue(1, MySocketIoSlowConsumerPolicy::Disconnect);
+
+        queue.enqueue(message("1", None));
+
+        assert_eq!(
+            EnqueueResult::SlowConsumer,
+            queue.enqueue(message("2", None))
+        );
+        assert_eq!(vec!["1"], drain(&queue));
+    }
+
+    #[test]
+    fn test_control_messages_bypass_high_water_mark() {
+        let queue = create_queue(1, MySocketIoSlowConsumerPolicy::Drop);
+
+        queue.enqueue(message("event", None));
+        queue.enqueue_control(message("ping", None));
+        queue.enqueue_control(message("ack", None));
+
+        assert_eq!(vec!["event", "ping", "ack"], drain(&queue));
+    }
+
+    #[test]
+    fn test_enqueue_latest_replaces_below_high_water_mark() {
+        let queue = create_queue(10, MySocketIoSlowConsumerPolicy::Drop);
+
+        queue.enqueue_latest(message("price-1", Some("price")));
+        queue.enqueue_latest(message("order", Some("order")));
+
+        assert_eq!(
+            EnqueueResult::Coalesced,
+            queue.enqueue_latest(message("price-2", Some("price")))
+        );
+        assert_eq!(vec!["price-2", "order"], drain(&queue));
+    }
+
+    #[test]
+    fn test_enqueue_latest_without_queued_key_follows_policy() {
+        let queue = create_queue(1, MySocketIoSlowConsumerPolicy::Drop);
+
+        queue.enqueue_latest(message("price-1", Some("price")));
+
+        assert_eq!(
+            EnqueueResult::Dropped,
+            queue.enqueue_latest(message("order", Some("order")))
+        );
+        assert_eq!(vec!["price-1"], drain(&queue));
+    }
+}
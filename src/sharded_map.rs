@@ -0,0 +1,72 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::RwLock,
+};
+
+const SHARDS_COUNT: usize = 64;
+
+// Each key is guarded by the lock of its own shard, so a reader is blocked only by writers
+// of the same shard and never for longer than one HashMap operation
+pub struct ShardedMap<TKey: Hash + Eq, TValue: Clone> {
+    shards: Vec<RwLock<HashMap<TKey, TValue>>>,
+}
+
+impl<TKey: Hash + Eq, TValue: Clone> ShardedMap<TKey, TValue> {
+    pub fn new() -> Self {
+        let mut shards = Vec::with_capacity(SHARDS_COUNT);
+        for _ in 0..SHARDS_COUNT {
+            shards.push(RwLock::new(HashMap::new()));
+        }
+
+        Self { shards }
+    }
+
+    fn get_shard<TQuery: Hash + ?Sized>(&self, key: &TQuery) -> &RwLock<HashMap<TKey, TValue>> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % SHARDS_COUNT]
+    }
+
+    pub fn get<TQuery>(&self, key: &TQuery) -> Option<TValue>
+    where
+        TKey: std::borrow::Borrow<TQuery>,
+        TQuery: Hash + Eq + ?Sized,
+    {
+        let read_access = self.get_shard(key).read().unwrap();
+        read_access.get(key).cloned()
+    }
+
+    pub fn insert(&self, key: TKey, value: TValue) -> Option<TValue> {
+        let mut write_access = self.get_shard(&key).write().unwrap();
+        write_access.insert(key, value)
+    }
+
+    pub fn remove<TQuery>(&self, key: &TQuery) -> Option<TValue>
+    where
+        TKey: std::borrow::Borrow<TQuery>,
+        TQuery: Hash + Eq + ?Sized,
+    {
+        let mut write_access = self.get_shard(key).write().unwrap();
+        write_access.remove(key)
+    }
+
+    // Shards are read one by one, so it is not a snapshot of the whole map at one moment
+    pub fn get_values(&self) -> Vec<TValue> {
+        let mut result = Vec::new();
+
+        for shard in &self.shards {
+            let read_access = shard.read().unwrap();
+            result.extend(read_access.values().cloned());
+        }
+
+        result
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.read().unwrap().len())
+            .sum()
+    }
+}
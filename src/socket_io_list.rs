@@ -1,112 +1,237 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, net::IpAddr, sync::Arc, sync::Mutex};
 
 use my_http_server_web_sockets::MyWebSocket;
-use tokio::sync::RwLock;
 
-use crate::MySocketIoConnection;
-
-struct SocketIdListInner {
-    sockets_by_web_socket_id: HashMap<i64, Arc<MySocketIoConnection>>,
-    sockets_by_my_socket_io_id: HashMap<String, Arc<MySocketIoConnection>>,
-}
+use crate::{
+    sharded_map::ShardedMap,
+    users_index::{UsersIndex, UsersIndexBinding},
+    MySocketIoConnection, MySocketIoTransport,
+};
 
+// WebSocket frames do not go through the registry: connection is bound to the WebSocket session.
+// Lookups by sid go to one shard and never wait for the whole registry
 pub struct SocketIoList {
-    sockets: RwLock<SocketIdListInner>,
+    sockets_by_my_socket_io_id: ShardedMap<String, Arc<MySocketIoConnection>>,
+    connections_by_ip: Mutex<HashMap<IpAddr, usize>>,
+    users: Arc<Mutex<UsersIndex>>,
+    user_id_data_key: String,
 }
 
 impl SocketIoList {
-    pub fn new() -> Self {
+    pub fn new(user_id_data_key: &str) -> Self {
         Self {
-            sockets: RwLock::new(SocketIdListInner {
-                sockets_by_web_socket_id: HashMap::new(),
-                sockets_by_my_socket_io_id: HashMap::new(),
-            }),
+            sockets_by_my_socket_io_id: ShardedMap::new(),
+            connections_by_ip: Mutex::new(HashMap::new()),
+            users: Arc::new(Mutex::new(UsersIndex::new())),
+            user_id_data_key: user_id_data_key.to_string(),
         }
     }
 
-    pub async fn add_socket_io(&self, socket_io_connection: Arc<MySocketIoConnection>) {
-        let web_socket = socket_io_connection.get_web_socket().await;
-        let mut write_access = self.sockets.write().await;
-        write_access.sockets_by_my_socket_io_id.insert(
+    // Connection with remote_addr must have reserved its ip slot with try_reserve_ip_connection.
+    // The slot is released on remove
+    pub fn add_socket_io(&self, socket_io_connection: Arc<MySocketIoConnection>) {
+        self.sockets_by_my_socket_io_id.insert(
             socket_io_connection.id.clone(),
             socket_io_connection.clone(),
         );
 
-        if let Some(web_socket) = web_socket {
-            write_access
-                .sockets_by_web_socket_id
-                .insert(web_socket.id, socket_io_connection.clone());
-        }
+        socket_io_connection.bind_users_index(UsersIndexBinding {
+            user_id_data_key: self.user_id_data_key.clone(),
+            users: self.users.clone(),
+        });
+    }
+
+    // Same as set_data with the user id data key
+    pub fn set_user_id(&self, socket_io: &Arc<MySocketIoConnection>, user_id: &str) {
+        socket_io.set_data(self.user_id_data_key.as_str(), user_id.to_string());
     }
 
+    // WebSocket is swapped under the connection lock, so there is one connection socket at any time.
+    // The replaced socket is closed here and its disconnected callback is ignored: has_web_socket
+    // is false for it, so it can not take the connection down with it
     pub async fn assign_web_socket_to_socket_io(
         &self,
         socket_io_id: &str,
         web_socket: Arc<MyWebSocket>,
     ) -> Option<Arc<MySocketIoConnection>> {
-        let found = {
-            let mut write_access = self.sockets.write().await;
-
-            let found = {
-                if let Some(found) = write_access.sockets_by_my_socket_io_id.get(socket_io_id) {
-                    Some(found.clone())
-                } else {
-                    None
-                }
-            };
-
-            if let Some(found) = found {
-                write_access
-                    .sockets_by_web_socket_id
-                    .insert(web_socket.id, found.clone());
-                Some(found)
-            } else {
-                None
-            }
-        };
-
-        if let Some(found) = found {
-            found.add_web_socket(web_socket).await;
-            Some(found)
-        } else {
-            None
+        let found = self.sockets_by_my_socket_io_id.get(socket_io_id)?;
+
+        let replaced = found.add_web_socket(web_socket.clone()).await;
+
+        if let Some(replaced) = replaced {
+            tracing::debug!(
+                parent: &found.span,
+                old_ws_id = replaced.id,
+                new_ws_id = web_socket.id,
+                "Socket.IO WebSocket is replaced"
+            );
+            replaced.disconnect().await;
         }
+
+        Some(found)
+    }
+
+    pub fn get_by_socket_io_id(&self, socket_io_id: &str) -> Option<Arc<MySocketIoConnection>> {
+        self.sockets_by_my_socket_io_id.get(socket_io_id)
+    }
+
+    pub fn get_all(&self) -> Vec<Arc<MySocketIoConnection>> {
+        self.sockets_by_my_socket_io_id.get_values()
     }
 
-    pub async fn get_by_socket_io_id(
+    pub fn get_count(&self) -> usize {
+        self.sockets_by_my_socket_io_id.len()
+    }
+
+    pub fn filter(
         &self,
-        socket_io_id: &str,
-    ) -> Option<Arc<MySocketIoConnection>> {
-        let read_access = self.sockets.read().await;
-        let result = read_access.sockets_by_my_socket_io_id.get(socket_io_id)?;
-        Some(result.clone())
+        predicate: impl Fn(&MySocketIoConnection) -> bool,
+    ) -> Vec<Arc<MySocketIoConnection>> {
+        let mut result = self.get_all();
+        result.retain(|socket_io| predicate(socket_io));
+        result
     }
 
-    pub async fn get_by_web_socket_id(
+    pub fn get_by_transport(
         &self,
-        web_socket_io: i64,
-    ) -> Option<Arc<MySocketIoConnection>> {
-        let read_access = self.sockets.read().await;
-        let result = read_access.sockets_by_web_socket_id.get(&web_socket_io)?;
-        Some(result.clone())
+        transport: MySocketIoTransport,
+    ) -> Vec<Arc<MySocketIoConnection>> {
+        self.filter(|socket_io| socket_io.get_transport() == transport)
     }
 
-    pub async fn remove(&self, socket_io_id: &str) -> Option<Arc<MySocketIoConnection>> {
-        let removed_socket_io = {
-            let mut write_access = self.sockets.write().await;
-            write_access.sockets_by_my_socket_io_id.remove(socket_io_id)
+    pub fn get_by_data(
+        &self,
+        key: &str,
+        predicate: impl Fn(&str) -> bool,
+    ) -> Vec<Arc<MySocketIoConnection>> {
+        self.filter(|socket_io| match socket_io.get_data(key) {
+            Some(value) => predicate(value.as_str()),
+            None => false,
+        })
+    }
+
+    pub fn get_user_id_data_key(&self) -> &str {
+        self.user_id_data_key.as_str()
+    }
+
+    pub fn get_by_user_id(&self, user_id: &str) -> Vec<Arc<MySocketIoConnection>> {
+        let sids = {
+            let read_access = self.users.lock().unwrap();
+            read_access.get(user_id)
         };
 
-        if let Some(removed_socket_io) = &removed_socket_io {
-            let web_socket = removed_socket_io.disconnect().await;
-            if let Some(web_socket) = web_socket {
-                let mut write_access = self.sockets.write().await;
-                write_access.sockets_by_web_socket_id.remove(&web_socket.id);
+        sids.iter()
+            .filter_map(|sid| self.sockets_by_my_socket_io_id.get(sid.as_str()))
+            .collect()
+    }
+
+    pub fn get_users_count(&self) -> usize {
+        let read_access = self.users.lock().unwrap();
+        read_access.get_users_count()
+    }
+
+    pub async fn get_by_namespace(&self, nsp: &str) -> Vec<Arc<MySocketIoConnection>> {
+        let mut result = Vec::new();
+
+        for socket_io in self.get_all() {
+            if socket_io.has_namespace(nsp).await {
+                result.push(socket_io);
+            }
+        }
+
+        result
+    }
+
+    pub async fn get_by_room(&self, nsp: &str, room: &str) -> Vec<Arc<MySocketIoConnection>> {
+        let mut result = Vec::new();
+
+        for socket_io in self.get_all() {
+            if socket_io.is_in_room(nsp, room).await {
+                result.push(socket_io);
             }
-        } else {
-            return None;
         }
 
-        removed_socket_io
+        result
+    }
+
+    // Returns (web_socket_connections, long_polling_connections)
+    pub fn get_count_by_transport(&self) -> (usize, usize) {
+        let mut web_socket_connections = 0;
+        let mut long_polling_connections = 0;
+
+        for socket_io in self.get_all() {
+            if socket_io.in_web_socket_model() {
+                web_socket_connections += 1;
+            } else {
+                long_polling_connections += 1;
+            }
+        }
+
+        (web_socket_connections, long_polling_connections)
+    }
+
+    // Returns (total, max) of messages waiting in send queues
+    pub fn get_send_queue_depths(&self) -> (usize, usize) {
+        let mut total = 0;
+        let mut max = 0;
+
+        for socket_io in self.get_all() {
+            let len = socket_io.get_send_queue_len();
+            total += len;
+            if len > max {
+                max = len;
+            }
+        }
+
+        (total, max)
+    }
+
+    // Check and increment are done under one lock, so concurrent handshakes from one ip
+    // can not pass the limit together
+    pub fn try_reserve_ip_connection(&self, ip: IpAddr, max_connections: Option<usize>) -> bool {
+        let mut write_access = self.connections_by_ip.lock().unwrap();
+
+        let count = write_access.get(&ip).copied().unwrap_or(0);
+
+        if let Some(max_connections) = max_connections {
+            if count >= max_connections {
+                return false;
+            }
+        }
+
+        write_access.insert(ip, count + 1);
+        true
+    }
+
+    pub fn release_ip_connection(&self, ip: IpAddr) {
+        let mut write_access = self.connections_by_ip.lock().unwrap();
+        if let Some(count) = write_access.get_mut(&ip) {
+            *count -= 1;
+            if *count == 0 {
+                write_access.remove(&ip);
+            }
+        }
+    }
+
+    pub fn get_connections_count_by_ip(&self, ip: &IpAddr) -> usize {
+        let read_access = self.connections_by_ip.lock().unwrap();
+        match read_access.get(ip) {
+            Some(count) => *count,
+            None => 0,
+        }
+    }
+
+    pub async fn remove(&self, socket_io_id: &str) -> Option<Arc<MySocketIoConnection>> {
+        let removed_socket_io = self.sockets_by_my_socket_io_id.remove(socket_io_id)?;
+
+        removed_socket_io.unbind_users_index();
+
+        if let Some(remote_addr) = removed_socket_io.remote_addr {
+            self.release_ip_connection(remote_addr.ip());
+        }
+
+        removed_socket_io.disconnect().await;
+
+        Some(removed_socket_io)
     }
 }
@@ -2,22 +2,34 @@ use std::{sync::Arc, time::Duration};
 
 use rust_extensions::date_time::DateTimeAsMicroseconds;
 
-use crate::{MySocketIoConnection, MySocketIoConnectionsCallbacks, SocketIoList};
+use crate::{
+    namespaces::SocketIoNameSpaces, MySocketIoConnection, MySocketIoConnectionsCallbacks,
+    MySocketIoDisconnectReason, SocketIoList,
+};
 
 use socket_io_utils::my_socket_io_messages::MySocketIoMessage;
 
 pub async fn start(
     connect_events: Arc<dyn MySocketIoConnectionsCallbacks + Send + Sync + 'static>,
     sockets_list: Arc<SocketIoList>,
+    registered_sockets: Arc<SocketIoNameSpaces>,
     my_socket_io_connection: Arc<MySocketIoConnection>,
+    ping_interval: Duration,
     ping_timeout: Duration,
-    ping_disconnect: Duration,
 ) {
-    println!(
-        "Socket.IO {} started livness loop",
-        my_socket_io_connection.id
+    // Engine.IO heartbeat: ping every ping_interval, client is gone if nothing came within ping_timeout after it.
+    // Same for both transports, so a connection which stays on polling after a failed upgrade is kept alive
+    let disconnect_after = ping_interval + ping_timeout;
+
+    tracing::debug!(
+        parent: &my_socket_io_connection.span,
+        "Socket.IO livness loop started"
     );
 
+    let mut disconnected = my_socket_io_connection.subscribe_to_disconnect();
+
+    let mut reason = MySocketIoDisconnectReason::TransportClose;
+
     while my_socket_io_connection.is_connected() {
         let now = DateTimeAsMicroseconds::now();
 
@@ -25,22 +37,33 @@ pub async fn start(
 
         let duration = now.duration_since(last_incoming_moment);
 
-        if duration.as_positive_or_zero() >= ping_disconnect {
-            println!(
-                "Socket.IO {} disconnected because of ping timeout",
-                my_socket_io_connection.id
+        if duration.as_positive_or_zero() >= disconnect_after {
+            tracing::warn!(
+                parent: &my_socket_io_connection.span,
+                since_last_incoming = ?duration.as_positive_or_zero(),
+                "Socket.IO ping timeout"
             );
+            reason = MySocketIoDisconnectReason::PingTimeout;
             break;
         }
 
-        if my_socket_io_connection.in_web_socket_model() {
-            my_socket_io_connection
-                .send_message(&MySocketIoMessage::Ping)
-                .await;
-        }
+        // Polling client gets the ping with the pending or the next poll
+        my_socket_io_connection
+            .send_message(&MySocketIoMessage::Ping)
+            .await;
 
-        tokio::time::sleep(ping_timeout).await;
+        tokio::select! {
+            _ = tokio::time::sleep(ping_interval) => {}
+            _ = disconnected.changed() => {}
+        }
     }
 
-    crate::process_disconnect(&sockets_list, &my_socket_io_connection, &connect_events).await;
+    crate::process_disconnect(
+        &sockets_list,
+        &registered_sockets,
+        &my_socket_io_connection,
+        &connect_events,
+        reason,
+    )
+    .await;
 }
@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+use crate::{
+    namespaces::SocketIoNameSpaces, MySocketIoConnection, MySocketIoConnectionsCallbacks,
+    MySocketIoDisconnectReason, SocketIoList,
+};
+
+pub async fn start(
+    connect_events: Arc<dyn MySocketIoConnectionsCallbacks + Send + Sync + 'static>,
+    sockets_list: Arc<SocketIoList>,
+    registered_sockets: Arc<SocketIoNameSpaces>,
+    my_socket_io_connection: Arc<MySocketIoConnection>,
+) {
+    while my_socket_io_connection.is_connected() {
+        if my_socket_io_connection.is_slow_consumer() {
+            tracing::warn!(
+                parent: &my_socket_io_connection.span,
+                queue_len = my_socket_io_connection.get_send_queue_len(),
+                "Socket.IO slow consumer"
+            );
+
+            crate::process_disconnect(
+                &sockets_list,
+                &registered_sockets,
+                &my_socket_io_connection,
+                &connect_events,
+                MySocketIoDisconnectReason::SlowConsumer,
+            )
+            .await;
+            break;
+        }
+
+        my_socket_io_connection.flush_send_queue().await;
+        my_socket_io_connection.wait_for_outgoing_messages().await;
+    }
+}
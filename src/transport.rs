@@ -0,0 +1,14 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MySocketIoTransport {
+    WebSocket,
+    Polling,
+}
+
+impl MySocketIoTransport {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::WebSocket => "websocket",
+            Self::Polling => "polling",
+        }
+    }
+}
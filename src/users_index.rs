@@ -0,0 +1,170 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+// user id -> sids of all user tabs and devices
+pub struct UsersIndex {
+    sids_by_user: HashMap<String, Vec<String>>,
+    user_by_sid: HashMap<String, String>,
+}
+
+impl UsersIndex {
+    pub fn new() -> Self {
+        Self {
+            sids_by_user: HashMap::new(),
+            user_by_sid: HashMap::new(),
+        }
+    }
+
+    pub fn update(&mut self, sid: &str, user_id: Option<&str>) {
+        if self.user_by_sid.get(sid).map(|itm| itm.as_str()) == user_id {
+            return;
+        }
+
+        self.remove(sid);
+
+        if let Some(user_id) = user_id {
+            self.sids_by_user
+                .entry(user_id.to_string())
+                .or_default()
+                .push(sid.to_string());
+
+            self.user_by_sid
+                .insert(sid.to_string(), user_id.to_string());
+        }
+    }
+
+    pub fn remove(&mut self, sid: &str) {
+        let user_id = match self.user_by_sid.remove(sid) {
+            Some(user_id) => user_id,
+            None => return,
+        };
+
+        if let Some(sids) = self.sids_by_user.get_mut(user_id.as_str()) {
+            sids.retain(|itm| itm != sid);
+
+            if sids.is_empty() {
+                self.sids_by_user.remove(user_id.as_str());
+            }
+        }
+    }
+
+    pub fn get(&self, user_id: &str) -> Vec<String> {
+        match self.sids_by_user.get(user_id) {
+            Some(sids) => sids.clone(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn get_users_count(&self) -> usize {
+        self.sids_by_user.len()
+    }
+}
+
+// Given to a connection when it is added to the list. Every write of the user id data key
+// goes to the index, so set_data from any place keeps emit_to_user up to date
+#[derive(Clone)]
+pub struct UsersIndexBinding {
+    pub user_id_data_key: String,
+    pub users: Arc<Mutex<UsersIndex>>,
+}
+
+impl UsersIndexBinding {
+    pub fn update(&self, sid: &str, key: &str, value: Option<&String>) {
+        if key != self.user_id_data_key {
+            return;
+        }
+
+        let mut write_access = self.users.lock().unwrap();
+        write_access.update(sid, value.map(|itm| itm.as_str()));
+    }
+
+    pub fn remove(&self, sid: &str) {
+        let mut write_access = self.users.lock().unwrap();
+        write_access.remove(sid);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_user_with_several_connections() {
+        let mut index = UsersIndex::new();
+
+        index.update("sid1", Some("user1"));
+        index.update("sid2", Some("user1"));
+        index.update("sid3", Some("user2"));
+
+        assert_eq!(vec!["sid1", "sid2"], index.get("user1"));
+        assert_eq!(vec!["sid3"], index.get("user2"));
+        assert_eq!(2, index.get_users_count());
+    }
+
+    #[test]
+    fn test_same_user_id_is_not_duplicated() {
+        let mut index = UsersIndex::new();
+
+        index.update("sid1", Some("user1"));
+        index.update("sid1", Some("user1"));
+
+        assert_eq!(vec!["sid1"], index.get("user1"));
+    }
+
+    #[test]
+    fn test_user_id_change_moves_connection() {
+        let mut index = UsersIndex::new();
+
+        index.update("sid1", Some("user1"));
+        index.update("sid1", Some("user2"));
+
+        assert!(index.get("user1").is_empty());
+        assert_eq!(vec!["sid1"], index.get("user2"));
+        assert_eq!(1, index.get_users_count());
+    }
+
+    #[test]
+    fn test_user_id_removed_from_data() {
+        let mut index = UsersIndex::new();
+
+        index.update("sid1", Some("user1"));
+        index.update("sid1", None);
+
+        assert!(index.get("user1").is_empty());
+        assert_eq!(0, index.get_users_count());
+    }
+
+    #[test]
+    fn test_remove_keeps_other_connections_of_user() {
+        let mut index = UsersIndex::new();
+
+        index.update("sid1", Some("user1"));
+        index.update("sid2", Some("user1"));
+        index.remove("sid1");
+
+        assert_eq!(vec!["sid2"], index.get("user1"));
+
+        index.remove("sid2");
+        index.remove("unknown");
+
+        assert!(index.get("user1").is_empty());
+        assert_eq!(0, index.get_users_count());
+    }
+
+    #[test]
+    fn test_binding_updates_only_on_user_id_key() {
+        let binding = UsersIndexBinding {
+            user_id_data_key: "userId".to_string(),
+            users: Arc::new(Mutex::new(UsersIndex::new())),
+        };
+
+        binding.update("sid1", "tenant", Some(&"tenant1".to_string()));
+        binding.update("sid1", "userId", Some(&"user1".to_string()));
+
+        let read_access = binding.users.lock().unwrap();
+        assert!(read_access.get("tenant1").is_empty());
+        assert_eq!(vec!["sid1"], read_access.get("user1"));
+    }
+}
@@ -1,14 +1,25 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use hyper_tungstenite::tungstenite::Message;
-use my_http_server::HttpFailResult;
 use my_http_server_web_sockets::{MyWebSocket, WebSocketMessage};
-use my_json::json_reader::array_parser::ArrayToJsonObjectsSplitter;
 use socket_io_utils::SocketIoSettings;
+use tracing::Instrument;
 
 use crate::{
-    namespaces::SocketIoNameSpaces, MySocketIoConnection, MySocketIoConnectionsCallbacks,
-    SocketIoList,
+    namespaces::{NamespaceKind, SocketIoNameSpaces, INVALID_NAMESPACE_ERROR},
+    pending_dispatch_guard::PendingDispatchGuard,
+    MySocketIoAckResponder, MySocketIoConnection, MySocketIoConnectionsCallbacks,
+    MySocketIoDisconnectReason, MySocketIoEngineOptions, MySocketIoError, MySocketIoEvent,
+    MySocketIoMetrics, MySocketIoProtocolError, MySocketIoProtocolErrorPolicy,
+    MySocketIoRateLimitPolicy, SocketIoList, WebSocketSession, DEFAULT_NAMESPACE,
+    RATE_LIMIT_EXCEEDED_ERROR,
 };
 
 use socket_io_utils::{
@@ -16,7 +27,14 @@ use socket_io_utils::{
     my_socket_io_messages::{GrandAccessData, MySocketIoTextPayload},
 };
 
-const DEFAULT_NAMESPACE: &str = "/";
+pub const ENGINE_IO_CLOSE_PAYLOAD: &str = "1";
+
+fn get_message_size(message: &WebSocketMessage) -> usize {
+    match message {
+        WebSocketMessage::String(value) => value.len(),
+        WebSocketMessage::Binary(value) => value.len(),
+    }
+}
 
 fn get_nsp(value: &Option<String>) -> &str {
     if let Some(nsp) = &value {
@@ -26,87 +44,360 @@ fn get_nsp(value: &Option<String>) -> &str {
     }
 }
 
+async fn check_upgrade_timeout(
+    socket_io: Arc<MySocketIoConnection>,
+    web_socket_id: i64,
+    upgrade_timeout: Duration,
+) {
+    tokio::time::sleep(upgrade_timeout).await;
+
+    // Connection goes on with polling and its heartbeat from the livness loop of the handshake
+    if let Some(web_socket) = socket_io.cancel_upgrade(web_socket_id).await {
+        tracing::warn!(
+            parent: &socket_io.span,
+            ws_id = web_socket_id,
+            "Socket.IO upgrade timeout. Staying on polling"
+        );
+        web_socket.disconnect().await;
+    }
+}
+
+// "41" or "41/admin," - client leaves the namespace, the Engine.IO connection stays open
+fn get_namespace_disconnect(value: &str) -> Option<&str> {
+    let nsp = value.strip_prefix("41")?;
+
+    if nsp.is_empty() {
+        return Some(DEFAULT_NAMESPACE);
+    }
+
+    if !nsp.starts_with('/') {
+        return None;
+    }
+
+    nsp.split(',').next()
+}
+
 pub struct WebSocketCallbacks {
     pub socket_io_list: Arc<SocketIoList>,
     pub registered_sockets: Arc<SocketIoNameSpaces>,
     pub connections_callback: Arc<dyn MySocketIoConnectionsCallbacks + Send + Sync + 'static>,
     pub settings: Arc<SocketIoSettings>,
+    pub options: Arc<MySocketIoEngineOptions>,
+    pub shutting_down: AtomicBool,
+    pub pending_dispatches: Arc<AtomicUsize>,
+    pub protocol_errors: AtomicU64,
+    pub metrics: Arc<MySocketIoMetrics>,
 }
 
 impl WebSocketCallbacks {
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::Relaxed)
+    }
+
+    pub fn get_pending_dispatches(&self) -> usize {
+        self.pending_dispatches.load(Ordering::SeqCst)
+    }
+
+    pub fn get_protocol_errors_count(&self) -> u64 {
+        self.protocol_errors.load(Ordering::Relaxed)
+    }
+
     async fn callback_message(
         &self,
         socket_io: &Arc<MySocketIoConnection>,
         msg: MySocketIoTextPayload,
     ) {
+        let span = tracing::debug_span!(
+            parent: &socket_io.span,
+            "socket_io_event",
+            nsp = get_nsp(&msg.nsp),
+            ack_id = ?msg.id,
+        );
+
+        let pending_dispatch = Arc::new(PendingDispatchGuard::new(self.pending_dispatches.clone()));
+        self.dispatch_message(socket_io, msg, &pending_dispatch)
+            .instrument(span)
+            .await;
+    }
+
+    async fn dispatch_message(
+        &self,
+        socket_io: &Arc<MySocketIoConnection>,
+        msg: MySocketIoTextPayload,
+        pending_dispatch: &Arc<PendingDispatchGuard>,
+    ) {
+        let started = Instant::now();
         let nsp_str = get_nsp(&msg.nsp);
 
-        if let Some(socket) = self.registered_sockets.get(nsp_str).await {
-            let mut event_name = None;
-            let mut event_data = None;
-
-            let mut i = 0;
-            for data in msg.data.as_bytes().split_array_json_to_objects() {
-                let data = data.unwrap();
-                if i == 0 {
-                    event_name = Some(std::str::from_utf8(data).unwrap());
-                } else if i == 1 {
-                    event_data = Some(std::str::from_utf8(data).unwrap());
-                } else {
-                    break;
+        // Connect middlewares guard the namespace: events of a namespace which is not joined are dropped
+        if !socket_io.has_namespace(nsp_str).await {
+            tracing::debug!(
+                nsp = nsp_str,
+                "Socket.IO event of not joined namespace is dropped"
+            );
+            return;
+        }
+
+        if let Some(settings) = &self.options.rate_limit.per_connection {
+            if !socket_io.try_consume_rate_limit(settings, None) {
+                self.handle_rate_limited(socket_io, msg.nsp, msg.id).await;
+                return;
+            }
+        }
+
+        if let Some(entry) = self.registered_sockets.get(nsp_str) {
+            let event_packet = match crate::event_packet::parse_event_packet(
+                msg.data.as_str(),
+                self.options.max_event_packet_size,
+                self.options.max_event_args,
+            ) {
+                Ok(event_packet) => event_packet,
+                Err(err) => {
+                    self.handle_protocol_error(socket_io, msg.nsp, msg.id, err)
+                        .await;
+                    return;
                 }
+            };
 
-                i += 1;
+            if let Some(settings) = self
+                .options
+                .rate_limit
+                .per_event
+                .get(event_packet.event_name.as_str())
+            {
+                if !socket_io
+                    .try_consume_rate_limit(settings, Some(event_packet.event_name.as_str()))
+                {
+                    self.handle_rate_limited(socket_io, msg.nsp, msg.id).await;
+                    return;
+                }
             }
 
-            let event_name = event_name.unwrap();
-            let event_data = event_data.unwrap();
+            let mut event = MySocketIoEvent {
+                nsp: nsp_str.to_string(),
+                event_name: event_packet.event_name,
+                event_data: event_packet.event_data,
+                args: event_packet.args,
+                ack_requested: msg.id.is_some(),
+            };
 
-            if let Some(ack_data) = socket.on(&event_name, &event_data).await {
-                let ack_contract = MySocketIoMessage::Ack(MySocketIoTextPayload {
-                    nsp: msg.nsp,
-                    data: ack_data,
-                    id: msg.id,
-                });
+            self.metrics.event_in(nsp_str, event.event_name.as_str());
 
-                socket_io.send_message(&ack_contract).await;
+            tracing::debug!(event = event.event_name.as_str(), "Socket.IO event");
+
+            for middleware in entry.middlewares.iter() {
+                if let Err(err) = middleware.on_event(socket_io, &mut event).await {
+                    socket_io.send_error(msg.nsp, msg.id, &err).await;
+                    return;
+                }
+            }
+
+            let ack = msg.id.map(|id| {
+                MySocketIoAckResponder::new(
+                    socket_io.clone(),
+                    msg.nsp.clone(),
+                    id,
+                    started,
+                    pending_dispatch.clone(),
+                )
+            });
+
+            if let Err(err) = entry.socket.on(&event, ack.clone()).await {
+                match ack {
+                    Some(ack) => {
+                        ack.send_error(&err).await;
+                    }
+                    None => socket_io.send_error(msg.nsp, None, &err).await,
+                }
             }
         }
     }
+
+    async fn handle_protocol_error(
+        &self,
+        socket_io: &Arc<MySocketIoConnection>,
+        nsp: Option<String>,
+        id: Option<i64>,
+        err: MySocketIoProtocolError,
+    ) {
+        self.protocol_errors.fetch_add(1, Ordering::SeqCst);
+
+        match self.options.protocol_error_policy {
+            MySocketIoProtocolErrorPolicy::Ignore => {}
+            MySocketIoProtocolErrorPolicy::ReplyError => {
+                socket_io
+                    .send_error(nsp, id, &err.to_socket_io_error())
+                    .await;
+            }
+            MySocketIoProtocolErrorPolicy::Disconnect => {
+                crate::process_disconnect(
+                    &self.socket_io_list,
+                    &self.registered_sockets,
+                    socket_io,
+                    &self.connections_callback,
+                    MySocketIoDisconnectReason::ProtocolError,
+                )
+                .await;
+            }
+        }
+    }
+
+    async fn handle_rate_limited(
+        &self,
+        socket_io: &Arc<MySocketIoConnection>,
+        nsp: Option<String>,
+        id: Option<i64>,
+    ) {
+        self.metrics.rate_limited();
+
+        match self.options.rate_limit.policy {
+            MySocketIoRateLimitPolicy::Drop => {}
+            MySocketIoRateLimitPolicy::ErrorAck => {
+                socket_io
+                    .send_error(nsp, id, &MySocketIoError::new(RATE_LIMIT_EXCEEDED_ERROR))
+                    .await;
+            }
+            MySocketIoRateLimitPolicy::Disconnect => {
+                socket_io.send_disconnect_packets().await;
+                crate::process_disconnect(
+                    &self.socket_io_list,
+                    &self.registered_sockets,
+                    socket_io,
+                    &self.connections_callback,
+                    MySocketIoDisconnectReason::RateLimited,
+                )
+                .await;
+            }
+        }
+    }
+
+    // Returns false if the ip has reached max connections. Reserved slot is released when the connection is removed
+    pub fn try_reserve_ip_connection(&self, remote_addr: &SocketAddr) -> bool {
+        let reserved = self.socket_io_list.try_reserve_ip_connection(
+            remote_addr.ip(),
+            self.options.rate_limit.max_connections_per_ip,
+        );
+
+        if !reserved {
+            self.metrics.connection_rejected();
+        }
+
+        reserved
+    }
+
+    async fn connect_to_namespace(
+        &self,
+        socket_io: &Arc<MySocketIoConnection>,
+        nsp: Option<String>,
+    ) {
+        let nsp_str = get_nsp(&nsp);
+
+        let kind = match self.registered_sockets.acquire(nsp_str).await {
+            Some(kind) => kind,
+            None => {
+                socket_io
+                    .send_connect_error(nsp_str, &MySocketIoError::new(INVALID_NAMESPACE_ERROR))
+                    .await;
+                return;
+            }
+        };
+
+        let middlewares = match self.registered_sockets.get(nsp_str) {
+            Some(entry) => entry.middlewares,
+            None => Default::default(),
+        };
+
+        for middleware in middlewares.iter() {
+            if let Err(err) = middleware.on_connect(socket_io, nsp_str).await {
+                if kind == NamespaceKind::Dynamic {
+                    self.registered_sockets.release_dynamic(nsp_str).await;
+                }
+                socket_io.send_connect_error(nsp_str, &err).await;
+                return;
+            }
+        }
+
+        let already_connected = socket_io.has_namespace(nsp_str).await;
+
+        socket_io.add_namespace(nsp_str).await;
+
+        // Connection holds one reference of the dynamic namespace. It is released
+        // on namespace disconnect packet or in process_disconnect
+        if kind == NamespaceKind::Dynamic && already_connected {
+            self.registered_sockets.release_dynamic(nsp_str).await;
+        }
+
+        let granted_message = MySocketIoMessage::GrandAccess(GrandAccessData {
+            nsp,
+            sid: socket_io.id.clone(),
+        });
+
+        socket_io.send_message(&granted_message).await;
+    }
+
+    async fn disconnect_from_namespace(&self, socket_io: &Arc<MySocketIoConnection>, nsp: &str) {
+        if socket_io.remove_namespace(nsp).await {
+            tracing::debug!(parent: &socket_io.span, nsp, "Socket.IO namespace is left");
+            self.registered_sockets.release_dynamic(nsp).await;
+        }
+    }
 }
 
-#[async_trait::async_trait]
-impl my_http_server_web_sockets::MyWebSocketCallback for WebSocketCallbacks {
-    async fn connected(
+impl WebSocketCallbacks {
+    pub(crate) async fn connected(
         &self,
+        session: &WebSocketSession,
         my_web_socket: Arc<MyWebSocket>,
-        disconnect_timeout: Duration,
-    ) -> Result<(), HttpFailResult> {
-        #[cfg(feature = "debug_ws")]
-        println!("connected web_socket:{}", my_web_socket.id);
+    ) {
+        tracing::debug!(ws_id = my_web_socket.id, "WebSocket connected");
 
         if let Some(query_string) = my_web_socket.get_query_string() {
             let sid = query_string.get_optional("sid");
 
             if sid.is_none() {
-                let (socket_io, response) = crate::process_connect(
+                if self.is_shutting_down() || !self.try_reserve_ip_connection(&my_web_socket.addr) {
+                    my_web_socket.disconnect().await;
+                    return;
+                }
+
+                let (socket_io, response) = match crate::process_connect(
                     &self.connections_callback,
                     &self.socket_io_list,
                     &self.settings,
+                    &self.options,
+                    &self.metrics,
                     Some(my_web_socket.clone()),
+                    Some(my_web_socket.addr),
                 )
-                .await;
+                .await
+                {
+                    Ok(result) => result,
+                    Err(_) => {
+                        my_web_socket.disconnect().await;
+                        return;
+                    }
+                };
+
+                session.bind(socket_io.clone());
 
                 my_web_socket.send_message(Message::Text(response)).await;
 
+                tokio::spawn(super::socket_io_writer_loop::start(
+                    self.connections_callback.clone(),
+                    self.socket_io_list.clone(),
+                    self.registered_sockets.clone(),
+                    socket_io.clone(),
+                ));
+
                 tokio::spawn(super::socket_io_livness_loop::start(
                     self.connections_callback.clone(),
                     self.socket_io_list.clone(),
+                    self.registered_sockets.clone(),
                     socket_io,
-                    self.settings.get_ping_timeout(),
                     self.settings.get_ping_interval(),
+                    self.settings.get_ping_timeout(),
                 ));
-                return Ok(());
+                return;
             }
 
             let sid = sid.unwrap();
@@ -117,13 +408,15 @@ impl my_http_server_web_sockets::MyWebSocketCallback for WebSocketCallbacks {
                 .await
             {
                 Some(socket_io) => {
-                    tokio::spawn(super::socket_io_livness_loop::start(
-                        self.connections_callback.clone(),
-                        self.socket_io_list.clone(),
-                        socket_io,
-                        self.settings.get_ping_timeout(),
-                        self.settings.get_ping_interval(),
-                    ));
+                    session.bind(socket_io.clone());
+
+                    if !socket_io.in_web_socket_model() {
+                        tokio::spawn(check_upgrade_timeout(
+                            socket_io,
+                            my_web_socket.id,
+                            self.options.upgrade_timeout,
+                        ));
+                    }
                 }
                 None => {
                     my_web_socket
@@ -132,90 +425,352 @@ impl my_http_server_web_sockets::MyWebSocketCallback for WebSocketCallbacks {
                             sid.value,
                         )))
                         .await;
-
-                    return Ok(());
                 }
             };
         }
-
-        Ok(())
     }
 
-    async fn disconnected(&self, my_web_socket: Arc<MyWebSocket>) {
-        #[cfg(feature = "debug_ws")]
-        println!("disconnected web_socket:{}", my_web_socket.id);
-        let find_result = self
-            .socket_io_list
-            .get_by_web_socket_id(my_web_socket.id)
-            .await;
+    pub(crate) async fn disconnected(
+        &self,
+        session: &WebSocketSession,
+        my_web_socket: Arc<MyWebSocket>,
+    ) {
+        tracing::debug!(ws_id = my_web_socket.id, "WebSocket disconnected");
 
-        if let Some(socket_io) = find_result {
-            crate::process_disconnect(&self.socket_io_list, &socket_io, &self.connections_callback)
-                .await;
+        if let Some(socket_io) = session.get_connection() {
+            if !socket_io.has_web_socket(my_web_socket.id).await {
+                // Probe WebSocket of an upgrade which did not happen or a replaced one.
+                // See assign_web_socket_to_socket_io
+                socket_io.cancel_upgrade(my_web_socket.id).await;
+                return;
+            }
+
+            crate::process_disconnect(
+                &self.socket_io_list,
+                &self.registered_sockets,
+                socket_io,
+                &self.connections_callback,
+                MySocketIoDisconnectReason::TransportClose,
+            )
+            .await;
         }
     }
-    async fn on_message(&self, my_web_socket: Arc<MyWebSocket>, message: WebSocketMessage) {
-        #[cfg(feature = "debug_ws")]
-        println!("Websocket{}, MSG: {:?}", my_web_socket.id, message);
 
-        let socket_io = self
-            .socket_io_list
-            .get_by_web_socket_id(my_web_socket.id)
+    pub(crate) async fn on_message(
+        &self,
+        session: &WebSocketSession,
+        my_web_socket: Arc<MyWebSocket>,
+        message: WebSocketMessage,
+    ) {
+        tracing::trace!(ws_id = my_web_socket.id, message = ?message, "WebSocket message");
+
+        // Connection is bound in connected callback. WebSocket which has no Socket.IO connection
+        // (unknown sid, rejected handshake) is closed on any frame
+        let socket_io = match session.get_connection() {
+            Some(socket_io) => socket_io,
+            None => {
+                tracing::debug!(
+                    ws_id = my_web_socket.id,
+                    "WebSocket frame without Socket.IO connection"
+                );
+                my_web_socket
+                    .send_message(Message::Text(ENGINE_IO_CLOSE_PAYLOAD.to_string()))
+                    .await;
+                my_web_socket.disconnect().await;
+                return;
+            }
+        };
+
+        let message_size = get_message_size(&message);
+
+        // Frame is already buffered by the WebSocket reader at this point. See max_http_buffer_size
+        if message_size > self.options.max_http_buffer_size {
+            tracing::warn!(
+                ws_id = my_web_socket.id,
+                size = message_size,
+                max_size = self.options.max_http_buffer_size,
+                "WebSocket frame exceeds max http buffer size"
+            );
+
+            my_web_socket
+                .send_message(Message::Text(ENGINE_IO_CLOSE_PAYLOAD.to_string()))
+                .await;
+
+            crate::process_disconnect(
+                &self.socket_io_list,
+                &self.registered_sockets,
+                socket_io,
+                &self.connections_callback,
+                MySocketIoDisconnectReason::PayloadTooLarge,
+            )
             .await;
 
-        if let Some(socket_io_ref) = socket_io.as_ref() {
-            socket_io_ref.update_incoming_activity();
+            return;
         }
 
-        if let WebSocketMessage::String(value) = &message {
-            if value == socket_io_utils::my_socket_io_messages::ENGINE_IO_PING_PROBE_PAYLOAD {
-                my_web_socket
-                    .send_message(Message::Text(
-                        socket_io_utils::my_socket_io_messages::ENGINE_IO_PONG_PROBE_PAYLOAD
-                            .to_string(),
-                    ))
-                    .await;
+        socket_io.update_incoming_activity();
+
+        let value = match self.options.parser.decode(message) {
+            Ok(Some(value)) => value,
+            Ok(None) => return,
+            Err(err) => {
+                self.handle_protocol_error(socket_io, None, None, err).await;
                 return;
             }
+        };
 
-            if value == socket_io_utils::my_socket_io_messages::ENGINE_IO_UPGRADE_PAYLOAD {
-                if let Some(socket_io) = socket_io.as_ref() {
-                    socket_io.upgrade_to_websocket().await;
-                } else {
-                    my_web_socket
-                        .send_message(Message::Text("SocketIo not found".to_string()))
-                        .await;
-                    my_web_socket.disconnect().await;
-                }
+        self.metrics.bytes_received(message_size);
+
+        if value == socket_io_utils::my_socket_io_messages::ENGINE_IO_PING_PROBE_PAYLOAD {
+            my_web_socket
+                .send_message(Message::Text(
+                    socket_io_utils::my_socket_io_messages::ENGINE_IO_PONG_PROBE_PAYLOAD
+                        .to_string(),
+                ))
+                .await;
+
+            socket_io.pause_polling().await;
+            return;
+        }
+
+        if value == socket_io_utils::my_socket_io_messages::ENGINE_IO_UPGRADE_PAYLOAD {
+            if !socket_io.upgrade_to_websocket(my_web_socket.id).await {
+                tracing::debug!(
+                    parent: &socket_io.span,
+                    ws_id = my_web_socket.id,
+                    "Socket.IO upgrade packet is ignored"
+                );
                 return;
             }
 
-            if let Some(message) = MySocketIoMessage::parse(value.as_str()) {
-                match message {
-                    MySocketIoMessage::Message(message) => {
-                        if let Some(socket_io_connection) = socket_io.as_ref() {
-                            self.callback_message(socket_io_connection, message).await;
-                        }
-                    }
-                    MySocketIoMessage::RequestAccess(nsp) => {
-                        if let Some(socket_io_connection) = socket_io.as_ref() {
-                            let nsp_str = get_nsp(&nsp);
-
-                            if self.registered_sockets.has_nsp(nsp_str).await {
-                                let granted_message =
-                                    MySocketIoMessage::GrandAccess(GrandAccessData {
-                                        nsp,
-                                        sid: socket_io_connection.id.clone(),
-                                    });
-
-                                socket_io_connection.send_message(&granted_message).await;
-                            }
+            // Livness loop started with the polling handshake keeps running on the WebSocket
+            self.metrics.upgrade();
+            return;
+        }
+
+        self.handle_socket_io_packet(socket_io, value.as_str())
+            .await;
+    }
+
+    // Body of a polling POST: Engine.IO packets separated by the record separator.
+    // Every packet goes to the same dispatch as a WebSocket frame, so acks and events work on polling too
+    pub(crate) async fn on_polling_payload(
+        &self,
+        socket_io: &Arc<MySocketIoConnection>,
+        payload: &str,
+    ) {
+        socket_io.update_incoming_activity();
+        self.metrics.bytes_received(payload.len());
+
+        for packet in payload.split(crate::my_socket_io_connection::ENGINE_IO_PAYLOAD_SEPARATOR) {
+            if packet == ENGINE_IO_CLOSE_PAYLOAD {
+                crate::process_disconnect(
+                    &self.socket_io_list,
+                    &self.registered_sockets,
+                    socket_io,
+                    &self.connections_callback,
+                    MySocketIoDisconnectReason::TransportClose,
+                )
+                .await;
+                return;
+            }
+
+            self.handle_socket_io_packet(socket_io, packet).await;
+        }
+    }
+
+    async fn handle_socket_io_packet(&self, socket_io: &Arc<MySocketIoConnection>, value: &str) {
+        if let Some(nsp) = get_namespace_disconnect(value) {
+            self.disconnect_from_namespace(socket_io, nsp).await;
+            return;
+        }
+
+        if let Some(message) = MySocketIoMessage::parse(value) {
+            match message {
+                MySocketIoMessage::Message(message) => {
+                    self.callback_message(socket_io, message).await;
+                }
+                MySocketIoMessage::RequestAccess(nsp) => {
+                    self.connect_to_namespace(socket_io, nsp).await;
+                }
+                MySocketIoMessage::Ack(ack) => {
+                    if let Some(id) = ack.id {
+                        if !socket_io.resolve_ack(id, ack.data) {
+                            tracing::debug!(
+                                parent: &socket_io.span,
+                                ack_id = id,
+                                "Socket.IO ack without pending request"
+                            );
                         }
                     }
-
-                    _ => {}
                 }
+
+                _ => {}
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use my_http_server::HttpFailResult;
+
+    use super::*;
+    use crate::{
+        MySocketIoEngineMiddleware, MySocketIoJsonParser, MySocketIoMiddleware,
+        MySocketIoSendQueueOptions, NamespaceRouter,
+    };
+
+    struct TestConnectionsCallbacks;
+
+    #[async_trait::async_trait]
+    impl MySocketIoConnectionsCallbacks for TestConnectionsCallbacks {
+        async fn connected(
+            &self,
+            _socket_io: Arc<MySocketIoConnection>,
+        ) -> Result<(), HttpFailResult> {
+            Ok(())
+        }
+
+        async fn disconnected(&self, _socket_io: Arc<MySocketIoConnection>) {}
+    }
+
+    struct RejectConnect;
+
+    #[async_trait::async_trait]
+    impl MySocketIoMiddleware for RejectConnect {
+        async fn on_connect(
+            &self,
+            _socket_io: &Arc<MySocketIoConnection>,
+            _nsp: &str,
+        ) -> Result<(), MySocketIoError> {
+            Err(MySocketIoError::new("Unauthorized"))
+        }
+    }
+
+    async fn create_engine(nsp: &str, calls: Arc<AtomicUsize>) -> MySocketIoEngineMiddleware {
+        let engine = MySocketIoEngineMiddleware::new(Arc::new(TestConnectionsCallbacks));
+
+        let mut router = NamespaceRouter::new(nsp);
+        router.on_raw("evt", move |_event, _ack| {
+            let calls = calls.clone();
+            async move {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        });
+        engine.register_socket_io(Arc::new(router)).await;
+
+        engine
+    }
+
+    fn add_connection(engine: &MySocketIoEngineMiddleware, sid: &str) -> Arc<MySocketIoConnection> {
+        let socket_io = Arc::new(MySocketIoConnection::new(
+            sid.to_string(),
+            None,
+            None,
+            MySocketIoSendQueueOptions::default(),
+            Arc::new(MySocketIoJsonParser),
+            Arc::new(MySocketIoMetrics::new()),
+        ));
+
+        engine.get_socket_io_list().add_socket_io(socket_io.clone());
+        socket_io
+    }
+
+    #[tokio::test]
+    async fn test_event_of_joined_namespace_is_dispatched() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let engine = create_engine("/admin", calls.clone()).await;
+        let socket_io = add_connection(&engine, "sid1");
+
+        assert!(engine.receive_packets("sid1", "40/admin,").await);
+        assert!(socket_io.has_namespace("/admin").await);
+
+        assert!(engine.receive_packets("sid1", "42/admin,[\"evt\",1]").await);
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_event_of_not_joined_namespace_is_dropped() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let engine = create_engine("/admin", calls.clone()).await;
+        add_connection(&engine, "sid1");
+
+        assert!(engine.receive_packets("sid1", "42/admin,[\"evt\",1]").await);
+        assert_eq!(0, calls.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_event_after_rejected_connect_is_dropped() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let engine = create_engine("/admin", calls.clone()).await;
+        engine
+            .register_middleware("/admin", Arc::new(RejectConnect))
+            .await;
+        let socket_io = add_connection(&engine, "sid1");
+
+        assert!(engine.receive_packets("sid1", "40/admin,").await);
+        assert!(!socket_io.has_namespace("/admin").await);
+
+        assert!(engine.receive_packets("sid1", "42/admin,[\"evt\",1]").await);
+        assert_eq!(0, calls.load(Ordering::SeqCst));
+    }
+
+    async fn create_engine_keeping_acks(
+        acks: Arc<std::sync::Mutex<Vec<MySocketIoAckResponder>>>,
+    ) -> MySocketIoEngineMiddleware {
+        let engine = MySocketIoEngineMiddleware::new(Arc::new(TestConnectionsCallbacks));
+
+        let mut router = NamespaceRouter::new(DEFAULT_NAMESPACE);
+        router.on_raw("evt", move |_event, ack| {
+            let acks = acks.clone();
+            async move {
+                acks.lock().unwrap().extend(ack);
+                Ok(())
+            }
+        });
+        engine.register_socket_io(Arc::new(router)).await;
+
+        engine
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_is_pending_until_ack_is_sent() {
+        let acks = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let engine = create_engine_keeping_acks(acks.clone()).await;
+        add_connection(&engine, "sid1");
+
+        assert!(engine.receive_packets("sid1", "40").await);
+        assert!(engine.receive_packets("sid1", "421[\"evt\",1]").await);
+
+        assert_eq!(1, engine.get_metrics_snapshot().await.pending_dispatches);
+
+        let ack = acks.lock().unwrap().pop().unwrap();
+        assert!(ack.clone().send("[]".to_string()).await);
+
+        assert_eq!(0, engine.get_metrics_snapshot().await.pending_dispatches);
+
+        drop(ack);
+        assert_eq!(0, engine.get_metrics_snapshot().await.pending_dispatches);
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_is_released_when_ack_is_dropped() {
+        let acks = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let engine = create_engine_keeping_acks(acks.clone()).await;
+        add_connection(&engine, "sid1");
+
+        assert!(engine.receive_packets("sid1", "40").await);
+        assert!(engine.receive_packets("sid1", "42[\"evt\",1]").await);
+        assert!(engine.receive_packets("sid1", "421[\"evt\",1]").await);
+
+        assert_eq!(1, engine.get_metrics_snapshot().await.pending_dispatches);
+
+        acks.lock().unwrap().clear();
+
+        assert_eq!(0, engine.get_metrics_snapshot().await.pending_dispatches);
+    }
+}
@@ -0,0 +1,54 @@
+use std::{
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use my_http_server::HttpFailResult;
+use my_http_server_web_sockets::{MyWebSocket, WebSocketMessage};
+
+use crate::{MySocketIoConnection, WebSocketCallbacks};
+
+// Callback of one WebSocket. Socket.IO connection is bound once, so frames are dispatched without registry lookups
+pub struct WebSocketSession {
+    callbacks: Arc<WebSocketCallbacks>,
+    connection: OnceLock<Arc<MySocketIoConnection>>,
+}
+
+impl WebSocketSession {
+    pub fn new(callbacks: Arc<WebSocketCallbacks>) -> Self {
+        Self {
+            callbacks,
+            connection: OnceLock::new(),
+        }
+    }
+
+    pub fn bind(&self, connection: Arc<MySocketIoConnection>) {
+        let _ = self.connection.set(connection);
+    }
+
+    pub fn get_connection(&self) -> Option<&Arc<MySocketIoConnection>> {
+        self.connection.get()
+    }
+}
+
+#[async_trait::async_trait]
+impl my_http_server_web_sockets::MyWebSocketCallback for WebSocketSession {
+    async fn connected(
+        &self,
+        my_web_socket: Arc<MyWebSocket>,
+        _disconnect_timeout: Duration,
+    ) -> Result<(), HttpFailResult> {
+        self.callbacks.connected(self, my_web_socket).await;
+        Ok(())
+    }
+
+    async fn disconnected(&self, my_web_socket: Arc<MyWebSocket>) {
+        self.callbacks.disconnected(self, my_web_socket).await;
+    }
+
+    async fn on_message(&self, my_web_socket: Arc<MyWebSocket>, message: WebSocketMessage) {
+        self.callbacks
+            .on_message(self, my_web_socket, message)
+            .await;
+    }
+}